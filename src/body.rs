@@ -0,0 +1,168 @@
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use error::Error;
+
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// The payload of a request, either held in memory or read from a stream as it is sent
+#[derive(Clone)]
+pub struct RequestBody {
+    kind: BodyKind,
+}
+
+#[derive(Clone)]
+enum BodyKind {
+    Bytes(Vec<u8>),
+    Reader(Arc<Mutex<Option<Box<dyn Read + Send>>>>, Option<u64>),
+}
+
+impl RequestBody {
+    /// Returns a body of in-memory bytes
+    pub fn from_bytes(data: Vec<u8>) -> RequestBody {
+        RequestBody {
+            kind: BodyKind::Bytes(data),
+        }
+    }
+
+    /// Returns a body that is read from `reader` while the request is sent.
+    ///
+    /// When `length` is known the body is sent with a Content-Length, otherwise
+    /// it is sent with chunked transfer encoding. A reader body can only be sent once.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use hasty::RequestBody;
+    ///
+    /// let body = RequestBody::from_reader(Cursor::new(vec![1, 2, 3]), None);
+    /// assert_eq!(body.content_length(), None);
+    /// ```
+    pub fn from_reader<R: Read + Send + 'static>(reader: R, length: Option<u64>) -> RequestBody {
+        RequestBody {
+            kind: BodyKind::Reader(Arc::new(Mutex::new(Some(Box::new(reader)))), length),
+        }
+    }
+
+    /// Returns the size of the body, if it is known ahead of time
+    pub fn content_length(&self) -> Option<u64> {
+        match self.kind {
+            BodyKind::Bytes(ref data) => Some(data.len() as u64),
+            BodyKind::Reader(_, length) => length,
+        }
+    }
+
+    /// Returns whether the body can be sent more than once
+    pub fn is_replayable(&self) -> bool {
+        match self.kind {
+            BodyKind::Bytes(_) => true,
+            BodyKind::Reader(..) => false,
+        }
+    }
+
+    /// Writes the body, using chunked framing when the size is unknown
+    pub fn write_to<W: Write>(&self, out: &mut W) -> Result<(), Error> {
+        match self.kind {
+            BodyKind::Bytes(ref data) => out.write_all(data).map_err(Error::from),
+            BodyKind::Reader(ref reader, length) => {
+                let taken = reader.lock().ok().and_then(|mut guard| guard.take());
+                let mut reader = taken.ok_or(Error::Io(::std::io::Error::new(
+                    ErrorKind::Other,
+                    "Request body has already been sent",
+                )))?;
+                match length {
+                    Some(length) => write_sized(&mut reader, length, out),
+                    None => write_chunked(&mut reader, out),
+                }
+            }
+        }
+    }
+}
+
+impl From<Vec<u8>> for RequestBody {
+    fn from(data: Vec<u8>) -> RequestBody {
+        RequestBody::from_bytes(data)
+    }
+}
+
+impl fmt::Debug for RequestBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            BodyKind::Bytes(ref data) => write!(f, "RequestBody({} bytes)", data.len()),
+            BodyKind::Reader(_, Some(length)) => write!(f, "RequestBody(reader, {} bytes)", length),
+            BodyKind::Reader(_, None) => write!(f, "RequestBody(reader)"),
+        }
+    }
+}
+
+fn read_some<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    loop {
+        match reader.read(buf) {
+            Ok(n) => return Ok(n),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::from(e)),
+        }
+    }
+}
+
+fn write_sized<R: Read + ?Sized, W: Write>(
+    reader: &mut R,
+    length: u64,
+    out: &mut W,
+) -> Result<(), Error> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut remaining = length;
+    while remaining > 0 {
+        let want = usize::min(buf.len(), remaining as usize);
+        let read = read_some(reader, &mut buf[..want])?;
+        if read == 0 {
+            return Err(Error::Io(::std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Request body ended {} bytes short", remaining),
+            )));
+        }
+        out.write_all(&buf[..read])?;
+        remaining -= read as u64;
+    }
+    Ok(())
+}
+
+fn write_chunked<R: Read + ?Sized, W: Write>(reader: &mut R, out: &mut W) -> Result<(), Error> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = read_some(reader, &mut buf)?;
+        if read == 0 {
+            out.write_all(b"0\r\n\r\n")?;
+            return Ok(());
+        }
+        out.write_all(format!("{:x}\r\n", read).as_bytes())?;
+        out.write_all(&buf[..read])?;
+        out.write_all(b"\r\n")?;
+    }
+}
+
+#[test]
+fn body_chunked_reader() {
+    use std::io::Cursor;
+
+    let body = RequestBody::from_reader(Cursor::new(b"hello world".to_vec()), None);
+    let mut out = Vec::new();
+    body.write_to(&mut out).unwrap();
+    assert_eq!(&out[..], &b"b\r\nhello world\r\n0\r\n\r\n"[..]);
+    assert!(body.write_to(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn body_sized_reader() {
+    use std::io::Cursor;
+
+    let body = RequestBody::from_reader(Cursor::new(b"hello world".to_vec()), Some(5));
+    let mut out = Vec::new();
+    body.write_to(&mut out).unwrap();
+    assert_eq!(&out[..], b"hello");
+
+    let short = RequestBody::from_reader(Cursor::new(b"hi".to_vec()), Some(5));
+    assert!(short.write_to(&mut Vec::new()).is_err());
+}
@@ -1,7 +1,19 @@
+use std::collections::HashMap;
 use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use url::Url;
 
+use identity::ClientIdentity;
+use pinning::Pin;
+use proxy::Proxy;
+use redirect::RedirectPolicy;
+use revocation::{CrlSource, RevocationMode};
+use rustls::ClientConfig;
+use trust::RootSource;
+
 pub trait Connection {
     fn connect(url: &Url) -> Result<TcpStream, String>;
 }
@@ -9,6 +21,26 @@ pub trait Connection {
 #[derive(Clone)]
 pub struct Config {
     allow_insecure_https: bool,
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Duration,
+    redirect_policy: RedirectPolicy,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    low_speed_limit: Option<(u64, Duration)>,
+    proxy: Option<Proxy>,
+    root_sources: Vec<RootSource>,
+    pins: HashMap<String, Vec<Pin>>,
+    pin_fallback: bool,
+    crl_sources: Vec<CrlSource>,
+    revocation_mode: RevocationMode,
+    client_identity: Option<ClientIdentity>,
+    tls_server_name: Option<String>,
+    tls_config: Option<Arc<ClientConfig>>,
+    tls_customizer: Option<Arc<dyn Fn(&mut ClientConfig) + Send + Sync>>,
+    session_cache_size: usize,
+    session_cache_file: Option<PathBuf>,
 }
 
 impl Config {
@@ -16,6 +48,26 @@ impl Config {
     pub fn new() -> Config {
         Config {
             allow_insecure_https: false,
+            pool_max_idle_per_host: 4,
+            pool_idle_timeout: Duration::from_secs(90),
+            redirect_policy: RedirectPolicy::None,
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            request_timeout: None,
+            low_speed_limit: None,
+            proxy: None,
+            root_sources: vec![RootSource::System],
+            pins: HashMap::new(),
+            pin_fallback: false,
+            crl_sources: Vec::new(),
+            revocation_mode: RevocationMode::SoftFail,
+            client_identity: None,
+            tls_server_name: None,
+            tls_config: None,
+            tls_customizer: None,
+            session_cache_size: 32,
+            session_cache_file: None,
         }
     }
 
@@ -51,6 +103,410 @@ impl Config {
     pub fn allows_insecure_https(&self) -> bool {
         self.allow_insecure_https
     }
+
+    /// Trusts the certificates in a PEM bundle file, or in every PEM file of a directory
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::Config;
+    /// let conf = Config::new().add_root_certificates_path("/etc/ssl/certs/ca-certificates.crt");
+    /// ```
+    pub fn add_root_certificates_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.root_sources.push(RootSource::Path(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Trusts the PEM encoded certificates in `pem`
+    pub fn add_root_certificates_pem(mut self, pem: &[u8]) -> Self {
+        self.root_sources.push(RootSource::Pem(pem.to_vec()));
+        self
+    }
+
+    /// Trusts a DER encoded certificate
+    pub fn add_root_certificate_der(mut self, der: &[u8]) -> Self {
+        self.root_sources.push(RootSource::Der(der.to_vec()));
+        self
+    }
+
+    /// Trusts the Mozilla root program bundle compiled into the crate
+    #[cfg(feature = "webpki-roots")]
+    pub fn with_builtin_roots(mut self) -> Self {
+        self.root_sources.push(RootSource::Builtin);
+        self
+    }
+
+    /// Stops trusting the certificates installed on the system, leaving only those added
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::Config;
+    /// let conf = Config::new().without_system_roots();
+    /// assert!(conf.root_sources().is_empty());
+    /// ```
+    pub fn without_system_roots(mut self) -> Self {
+        self.root_sources.retain(|s| match *s {
+            RootSource::System => false,
+            _ => true,
+        });
+        self
+    }
+
+    /// Returns where trust anchors for verifying https servers are loaded from
+    pub fn root_sources(&self) -> &[RootSource] {
+        &self.root_sources
+    }
+
+    /// Pins a key or certificate for `host`. A pinned host must present one of its pins,
+    /// so pinning the next key ahead of time lets it be rotated.
+    ///
+    /// A pinned server certificate needs no trust anchor, but must still be current and
+    /// issued for the host. A pinned issuer must still form a valid chain.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::{Config, Pin};
+    ///
+    /// let current = Pin::spki_sha256("rESDikx3klYrRENpoDOgnwtDmzzPzTos3XBarKfBO7s=").unwrap();
+    /// let next = Pin::spki_sha256("47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=").unwrap();
+    /// let config = Config::new().pin("api.example.com", current).pin("api.example.com", next);
+    /// assert_eq!(config.pins()["api.example.com"].len(), 2);
+    /// ```
+    pub fn pin(mut self, host: &str, pin: Pin) -> Self {
+        self.pins
+            .entry(host.to_lowercase())
+            .or_insert_with(Vec::new)
+            .push(pin);
+        self
+    }
+
+    /// Returns the pins of each pinned host
+    pub fn pins(&self) -> &HashMap<String, Vec<Pin>> {
+        &self.pins
+    }
+
+    /// Sets whether a pinned host that matches none of its pins may still be
+    /// accepted by normal chain validation, off by default
+    pub fn pin_fallback(mut self, fallback: bool) -> Self {
+        self.pin_fallback = fallback;
+        self
+    }
+
+    /// Returns whether pin mismatches fall back to chain validation
+    pub fn get_pin_fallback(&self) -> bool {
+        self.pin_fallback
+    }
+
+    /// Rejects server certificates listed as revoked in a CRL file, DER or PEM.
+    /// A list is only used once its signature checks out against the issuer's key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::{Config, RevocationMode};
+    /// let conf = Config::new()
+    ///     .add_crl_path("/etc/pki/crl/issuing-ca.crl")
+    ///     .revocation_mode(RevocationMode::HardFail);
+    /// assert_eq!(conf.crl_sources().len(), 1);
+    /// ```
+    pub fn add_crl_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.crl_sources.push(CrlSource::Path(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Rejects server certificates listed as revoked in one or more PEM encoded CRLs
+    pub fn add_crl_pem(mut self, pem: &[u8]) -> Self {
+        self.crl_sources.push(CrlSource::Pem(pem.to_vec()));
+        self
+    }
+
+    /// Rejects server certificates listed as revoked in a DER encoded CRL
+    pub fn add_crl_der(mut self, der: &[u8]) -> Self {
+        self.crl_sources.push(CrlSource::Der(der.to_vec()));
+        self
+    }
+
+    /// Returns where certificate revocation lists are loaded from
+    pub fn crl_sources(&self) -> &[CrlSource] {
+        &self.crl_sources
+    }
+
+    /// Sets whether a certificate without a current CRL from its issuer is accepted,
+    /// `RevocationMode::SoftFail` by default. Only applies once CRLs are added.
+    pub fn revocation_mode(mut self, mode: RevocationMode) -> Self {
+        self.revocation_mode = mode;
+        self
+    }
+
+    /// Returns how certificates without a current CRL are treated
+    pub fn get_revocation_mode(&self) -> RevocationMode {
+        self.revocation_mode
+    }
+
+    /// Presents a client certificate to https servers that ask for one
+    pub fn client_identity(mut self, identity: ClientIdentity) -> Self {
+        self.client_identity = Some(identity);
+        self
+    }
+
+    /// Returns the client certificate identity
+    pub fn get_client_identity(&self) -> Option<&ClientIdentity> {
+        self.client_identity.as_ref()
+    }
+
+    /// Sends `name` as SNI and verifies server certificates against it, for every https
+    /// request that does not set its own name with `Request::set_tls_server_name`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::Config;
+    /// // reach the service through its load balancer's address
+    /// let conf = Config::new().tls_server_name("api.example.com");
+    /// assert_eq!(conf.get_tls_server_name(), Some("api.example.com"));
+    /// ```
+    pub fn tls_server_name(mut self, name: &str) -> Self {
+        self.tls_server_name = Some(name.to_owned());
+        self
+    }
+
+    /// Returns the tls server name override
+    pub fn get_tls_server_name(&self) -> Option<&str> {
+        self.tls_server_name.as_ref().map(|n| n.as_str())
+    }
+
+    /// Uses a caller built rustls configuration for every https connection.
+    ///
+    /// It takes precedence over the configuration hasty would build, so the trust anchor,
+    /// pinning, revocation, client certificate, session cache, customizer and insecure
+    /// https settings cannot be combined with it: https requests then fail with
+    /// `Error::Config`. Verify servers through the verifier of `tls` instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use hasty::rustls::ClientConfig;
+    /// use hasty::Config;
+    ///
+    /// let mut tls = ClientConfig::new();
+    /// tls.alpn_protocols = vec!["http/1.1".to_owned()];
+    /// let shared = Arc::new(tls);
+    /// let config = Config::new().tls_config(shared.clone());
+    /// ```
+    pub fn tls_config(mut self, tls: Arc<ClientConfig>) -> Self {
+        self.tls_config = Some(tls);
+        self
+    }
+
+    /// Returns the caller built rustls configuration
+    pub fn get_tls_config(&self) -> Option<&Arc<ClientConfig>> {
+        self.tls_config.as_ref()
+    }
+
+    /// Adjusts the rustls configuration hasty builds from its settings, before it is used
+    ///
+    /// Session keys cannot be logged for `SSLKEYLOGFILE`, the `KeyLog` hook arrived in rustls 0.13
+    /// and hasty is built on rustls 0.11.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::Config;
+    ///
+    /// let config = Config::new().tls_customizer(|tls| {
+    ///     tls.alpn_protocols = vec!["http/1.1".to_owned()];
+    /// });
+    /// ```
+    pub fn tls_customizer<F>(mut self, customize: F) -> Self
+    where
+        F: Fn(&mut ClientConfig) + Send + Sync + 'static,
+    {
+        self.tls_customizer = Some(Arc::new(customize));
+        self
+    }
+
+    /// Sets how many TLS sessions are kept for resumption, zero turns resumption off
+    pub fn session_cache_size(mut self, size: usize) -> Self {
+        self.session_cache_size = size;
+        self
+    }
+
+    /// Returns how many TLS sessions are kept for resumption
+    pub fn get_session_cache_size(&self) -> usize {
+        self.session_cache_size
+    }
+
+    /// Keeps TLS sessions in a file, so that they can be resumed after a restart.
+    /// The file holds resumption secrets and is only readable by its owner.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::Config;
+    /// let conf = Config::new().session_cache_file("/var/lib/agent/tls-sessions");
+    /// ```
+    pub fn session_cache_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.session_cache_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Returns the file TLS sessions are kept in
+    pub fn get_session_cache_file(&self) -> Option<&Path> {
+        self.session_cache_file.as_ref().map(|p| p.as_path())
+    }
+
+    /// Returns the rustls configuration customizer
+    pub fn get_tls_customizer(&self) -> Option<&Arc<dyn Fn(&mut ClientConfig) + Send + Sync>> {
+        self.tls_customizer.as_ref()
+    }
+
+    /// Sets how many idle keep-alive connections are kept for each scheme, host and port.
+    /// Setting it to zero disables connection reuse.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::Config;
+    /// let conf = Config::new().pool_max_idle_per_host(0);
+    /// assert_eq!(conf.get_pool_max_idle_per_host(), 0);
+    /// ```
+    pub fn pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = max_idle;
+        self
+    }
+
+    /// Returns how many idle connections are kept per host
+    pub fn get_pool_max_idle_per_host(&self) -> usize {
+        self.pool_max_idle_per_host
+    }
+
+    /// Sets how long an idle connection is kept before it is closed
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Returns how long an idle connection is kept
+    pub fn get_pool_idle_timeout(&self) -> Duration {
+        self.pool_idle_timeout
+    }
+
+    /// Sets whether redirect responses are followed, by default they are returned as is.
+    /// With `RedirectPolicy::Limited(0)` any redirect fails with `Error::TooManyRedirects`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::{Config, RedirectPolicy};
+    /// let conf = Config::new().redirect_policy(RedirectPolicy::Limited(5));
+    /// ```
+    pub fn redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect_policy = policy;
+        self
+    }
+
+    /// Returns the redirect policy
+    pub fn get_redirect_policy(&self) -> &RedirectPolicy {
+        &self.redirect_policy
+    }
+
+    /// Sets how long establishing a tcp connection may take
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use hasty::Config;
+    /// let conf = Config::new().connect_timeout(Duration::from_secs(5));
+    /// assert_eq!(conf.get_connect_timeout(), Some(Duration::from_secs(5)));
+    /// ```
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the connect timeout
+    pub fn get_connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    /// Sets how long a single read may wait for data to arrive
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the read timeout
+    pub fn get_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    /// Sets how long a single write may wait for the connection to accept data
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the write timeout
+    pub fn get_write_timeout(&self) -> Option<Duration> {
+        self.write_timeout
+    }
+
+    /// Sets how long a whole request may take, including redirects and reading the body
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the request timeout
+    pub fn get_request_timeout(&self) -> Option<Duration> {
+        self.request_timeout
+    }
+
+    /// Fails a request that moves fewer than `bytes_per_sec` bytes per second,
+    /// measured over each period of `time`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use hasty::Config;
+    /// // give up when less than 1KB/s arrives over 30 seconds
+    /// let conf = Config::new().low_speed_limit(1024, Duration::from_secs(30));
+    /// ```
+    pub fn low_speed_limit(mut self, bytes_per_sec: u64, time: Duration) -> Self {
+        self.low_speed_limit = Some((bytes_per_sec, time));
+        self
+    }
+
+    /// Returns the low speed limit, as bytes per second and the period it is measured over
+    pub fn get_low_speed_limit(&self) -> Option<(u64, Duration)> {
+        self.low_speed_limit
+    }
+
+    /// Sends requests through an http proxy
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Returns the proxy to use for requests to `host`, unless it is bypassed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::{Config, Proxy};
+    /// let proxy = Proxy::new("http://proxy.internal:3128").unwrap().bypass("localhost");
+    /// let conf = Config::new().proxy(proxy);
+    /// assert!(conf.proxy_for("localhost").is_none());
+    /// assert!(conf.proxy_for("www.rust-lang.org").is_some());
+    /// ```
+    pub fn proxy_for(&self, host: &str) -> Option<&Proxy> {
+        self.proxy.as_ref().filter(|p| !p.bypasses(host))
+    }
 }
 
 #[test]
@@ -1,169 +1,332 @@
 use std::fmt;
+use std::str::FromStr;
 
-#[derive(Clone, Debug)]
+use error::Error;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum HttpMethods {
     Get,
+    Head,
     Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    /// Any other method token, such as the WebDAV `PROPFIND`
+    Extension(String),
 }
 
 impl fmt::Display for HttpMethods {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s: String = format!("{:?}", self);
-        write!(f, "{}", s.to_uppercase())
+        match *self {
+            HttpMethods::Extension(ref method) => write!(f, "{}", method),
+            _ => {
+                let s: String = format!("{:?}", self);
+                write!(f, "{}", s.to_uppercase())
+            }
+        }
+    }
+}
+
+impl FromStr for HttpMethods {
+    type Err = Error;
+
+    /// Parses a method token, method names are case sensitive
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::HttpMethods;
+    ///
+    /// let method: HttpMethods = "PROPFIND".parse().unwrap();
+    /// assert_eq!(method, HttpMethods::Extension("PROPFIND".to_owned()));
+    /// assert!("BAD METHOD".parse::<HttpMethods>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<HttpMethods, Error> {
+        if s.is_empty() || !s.bytes().all(is_token_char) {
+            return Err(Error::Parse(format!("Invalid method: {}", s)));
+        }
+        Ok(match s {
+            "GET" => HttpMethods::Get,
+            "HEAD" => HttpMethods::Head,
+            "POST" => HttpMethods::Post,
+            "PUT" => HttpMethods::Put,
+            "DELETE" => HttpMethods::Delete,
+            "CONNECT" => HttpMethods::Connect,
+            "OPTIONS" => HttpMethods::Options,
+            "TRACE" => HttpMethods::Trace,
+            "PATCH" => HttpMethods::Patch,
+            _ => HttpMethods::Extension(s.to_owned()),
+        })
+    }
+}
+
+impl HttpMethods {
+    /// Returns whether repeating the request has the same effect as sending it once
+    /// (RFC 9110 section 9.2.2)
+    pub fn is_idempotent(&self) -> bool {
+        match *self {
+            HttpMethods::Get
+            | HttpMethods::Head
+            | HttpMethods::Put
+            | HttpMethods::Delete
+            | HttpMethods::Options
+            | HttpMethods::Trace => true,
+            _ => false,
+        }
+    }
+}
+
+/// Returns whether a byte is a valid `tchar` (RFC 9110 section 5.6.2)
+pub fn is_token_char(c: u8) -> bool {
+    match c {
+        b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => true,
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_'
+        | b'`' | b'|' | b'~' => true,
+        _ => false,
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ResponseCode {
-    // Testing
-    Undefined = 0,
+    /// No status was received
+    Undefined,
     // Informational
-    Continue = 100,
-    SwitchingProtocols = 101,
-    Processing = 102,
+    Continue,
+    SwitchingProtocols,
+    Processing,
     // Success
-    Ok = 200,
-    Created = 201,
-    Accepted = 202,
-    NonAuthoritativeInformation = 203,
-    NoContent = 204,
-    ResetContent = 205,
-    PartialContent = 206,
-    MultiStatus = 207,
-    AlreadyReported = 208,
-    ImUsed = 226,
+    Ok,
+    Created,
+    Accepted,
+    NonAuthoritativeInformation,
+    NoContent,
+    ResetContent,
+    PartialContent,
+    MultiStatus,
+    AlreadyReported,
+    ImUsed,
     // Redirection
-    MultipleChoices = 300,
-    MovedPermanently = 301,
-    Found = 302,
-    SeeOther = 303,
-    NotModified = 304,
-    UseProxy = 305,
-    SwitchProxy = 306,
-    TemporaryRedirect = 307,
-    PermanentRedirect = 308,
+    MultipleChoices,
+    MovedPermanently,
+    Found,
+    SeeOther,
+    NotModified,
+    UseProxy,
+    SwitchProxy,
+    TemporaryRedirect,
+    PermanentRedirect,
     // Client Error
-    BadRequest = 400,
-    Unauthorized = 401,
-    PaymentRequired = 402,
-    Forbidden = 403,
-    NotFound = 404,
-    MethodNotAllowed = 405,
-    NotAcceptable = 406,
-    ProxyAuthenticationRequired = 407,
-    RequestTimeout = 408,
-    Conflict = 409,
-    Gone = 410,
-    LengthRequired = 411,
-    PreconditionFailed = 412,
-    PayloadTooLarge = 413,
-    UriTooLong = 414,
-    UnsupportedMediaType = 415,
-    RangeNotSatisified = 416,
-    ExpectationFailed = 417,
-    ImATeapot = 418,
-    MisdirectedRequest = 421,
-    UnprocessableEntitiy = 422,
-    Locked = 423,
-    FailedDependency = 424,
-    UpgradeRequired = 426,
-    PreconditionRequired = 428,
-    TooManyRequests = 429,
-    RequestHeaderFieldsTooLarge = 431,
-    UnavailableForLegalReasons = 451,
+    BadRequest,
+    Unauthorized,
+    PaymentRequired,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    NotAcceptable,
+    ProxyAuthenticationRequired,
+    RequestTimeout,
+    Conflict,
+    Gone,
+    LengthRequired,
+    PreconditionFailed,
+    PayloadTooLarge,
+    UriTooLong,
+    UnsupportedMediaType,
+    RangeNotSatisified,
+    ExpectationFailed,
+    ImATeapot,
+    MisdirectedRequest,
+    UnprocessableEntitiy,
+    Locked,
+    FailedDependency,
+    UpgradeRequired,
+    PreconditionRequired,
+    TooManyRequests,
+    RequestHeaderFieldsTooLarge,
+    UnavailableForLegalReasons,
     // Server Error
-    InternalServerError = 500,
-    NotImplemented = 501,
-    BadGateway = 502,
-    ServiceUnavailable = 503,
-    GatewayTimeout = 504,
-    HttpVersionNotSupported = 505,
-    VariantAlsoNegotiates = 506,
-    InsufficientStorage = 507,
-    LoopDetected = 508,
-    NotExtended = 510,
-    NetworkAuthenticationRequired = 511,
+    InternalServerError,
+    NotImplemented,
+    BadGateway,
+    ServiceUnavailable,
+    GatewayTimeout,
+    HttpVersionNotSupported,
+    VariantAlsoNegotiates,
+    InsufficientStorage,
+    LoopDetected,
+    NotExtended,
+    NetworkAuthenticationRequired,
+    /// Any other status, such as 299 or 420
+    Other(u16),
 }
 
 impl fmt::Display for ResponseCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s: String = format!("{:?}", self).to_owned();
-        write!(f, "{}", &s.to_uppercase())
+        match *self {
+            ResponseCode::Other(code) => write!(f, "{}", code),
+            _ => {
+                let s: String = format!("{:?}", self).to_owned();
+                write!(f, "{}", &s.to_uppercase())
+            }
+        }
     }
 }
 
+// the registered statuses hasty names (RFC 9110 section 15 and extensions)
+const KNOWN_CODES: &[(u16, ResponseCode)] = &[
+    // Informational
+    (100, ResponseCode::Continue),
+    (101, ResponseCode::SwitchingProtocols),
+    (102, ResponseCode::Processing),
+    // Success
+    (200, ResponseCode::Ok),
+    (201, ResponseCode::Created),
+    (202, ResponseCode::Accepted),
+    (203, ResponseCode::NonAuthoritativeInformation),
+    (204, ResponseCode::NoContent),
+    (205, ResponseCode::ResetContent),
+    (206, ResponseCode::PartialContent),
+    (207, ResponseCode::MultiStatus),
+    (208, ResponseCode::AlreadyReported),
+    (226, ResponseCode::ImUsed),
+    // Redirection
+    (300, ResponseCode::MultipleChoices),
+    (301, ResponseCode::MovedPermanently),
+    (302, ResponseCode::Found),
+    (303, ResponseCode::SeeOther),
+    (304, ResponseCode::NotModified),
+    (305, ResponseCode::UseProxy),
+    (306, ResponseCode::SwitchProxy),
+    (307, ResponseCode::TemporaryRedirect),
+    (308, ResponseCode::PermanentRedirect),
+    // Client Error
+    (400, ResponseCode::BadRequest),
+    (401, ResponseCode::Unauthorized),
+    (402, ResponseCode::PaymentRequired),
+    (403, ResponseCode::Forbidden),
+    (404, ResponseCode::NotFound),
+    (405, ResponseCode::MethodNotAllowed),
+    (406, ResponseCode::NotAcceptable),
+    (407, ResponseCode::ProxyAuthenticationRequired),
+    (408, ResponseCode::RequestTimeout),
+    (409, ResponseCode::Conflict),
+    (410, ResponseCode::Gone),
+    (411, ResponseCode::LengthRequired),
+    (412, ResponseCode::PreconditionFailed),
+    (413, ResponseCode::PayloadTooLarge),
+    (414, ResponseCode::UriTooLong),
+    (415, ResponseCode::UnsupportedMediaType),
+    (416, ResponseCode::RangeNotSatisified),
+    (417, ResponseCode::ExpectationFailed),
+    (418, ResponseCode::ImATeapot),
+    (421, ResponseCode::MisdirectedRequest),
+    (422, ResponseCode::UnprocessableEntitiy),
+    (423, ResponseCode::Locked),
+    (424, ResponseCode::FailedDependency),
+    (426, ResponseCode::UpgradeRequired),
+    (428, ResponseCode::PreconditionRequired),
+    (429, ResponseCode::TooManyRequests),
+    (431, ResponseCode::RequestHeaderFieldsTooLarge),
+    (451, ResponseCode::UnavailableForLegalReasons),
+    // Server Error
+    (500, ResponseCode::InternalServerError),
+    (501, ResponseCode::NotImplemented),
+    (502, ResponseCode::BadGateway),
+    (503, ResponseCode::ServiceUnavailable),
+    (504, ResponseCode::GatewayTimeout),
+    (505, ResponseCode::HttpVersionNotSupported),
+    (506, ResponseCode::VariantAlsoNegotiates),
+    (507, ResponseCode::InsufficientStorage),
+    (508, ResponseCode::LoopDetected),
+    (510, ResponseCode::NotExtended),
+    (511, ResponseCode::NetworkAuthenticationRequired),
+];
+
 impl ResponseCode {
+    /// Returns the named status for `i`, `Other` for an unnamed status code and
+    /// `Undefined` for a number that is not a status code
     pub fn from_int(i: u32) -> ResponseCode {
-        match i {
-            // Testing
-            0 => ResponseCode::Undefined,
-            // Informational
-            100 => ResponseCode::Continue,
-            101 => ResponseCode::SwitchingProtocols,
-            102 => ResponseCode::Processing,
-            // Success
-            200 => ResponseCode::Ok,
-            201 => ResponseCode::Created,
-            202 => ResponseCode::Accepted,
-            203 => ResponseCode::NonAuthoritativeInformation,
-            204 => ResponseCode::NoContent,
-            205 => ResponseCode::ResetContent,
-            206 => ResponseCode::PartialContent,
-            207 => ResponseCode::MultiStatus,
-            208 => ResponseCode::AlreadyReported,
-            226 => ResponseCode::ImUsed,
-            // Redirection
-            300 => ResponseCode::MultipleChoices,
-            301 => ResponseCode::MovedPermanently,
-            302 => ResponseCode::Found,
-            303 => ResponseCode::SeeOther,
-            304 => ResponseCode::NotModified,
-            305 => ResponseCode::UseProxy,
-            306 => ResponseCode::SwitchProxy,
-            307 => ResponseCode::TemporaryRedirect,
-            308 => ResponseCode::PermanentRedirect,
-            // Client Error
-            400 => ResponseCode::BadRequest,
-            401 => ResponseCode::Unauthorized,
-            402 => ResponseCode::PaymentRequired,
-            403 => ResponseCode::Forbidden,
-            404 => ResponseCode::NotFound,
-            405 => ResponseCode::MethodNotAllowed,
-            406 => ResponseCode::NotAcceptable,
-            407 => ResponseCode::ProxyAuthenticationRequired,
-            408 => ResponseCode::RequestTimeout,
-            409 => ResponseCode::Conflict,
-            410 => ResponseCode::Gone,
-            411 => ResponseCode::LengthRequired,
-            412 => ResponseCode::PreconditionFailed,
-            413 => ResponseCode::PayloadTooLarge,
-            414 => ResponseCode::UriTooLong,
-            415 => ResponseCode::UnsupportedMediaType,
-            416 => ResponseCode::RangeNotSatisified,
-            417 => ResponseCode::ExpectationFailed,
-            418 => ResponseCode::ImATeapot,
-            421 => ResponseCode::MisdirectedRequest,
-            422 => ResponseCode::UnprocessableEntitiy,
-            423 => ResponseCode::Locked,
-            424 => ResponseCode::FailedDependency,
-            426 => ResponseCode::UpgradeRequired,
-            428 => ResponseCode::PreconditionRequired,
-            429 => ResponseCode::TooManyRequests,
-            431 => ResponseCode::RequestHeaderFieldsTooLarge,
-            451 => ResponseCode::UnavailableForLegalReasons,
-            // Server Error
-            500 => ResponseCode::InternalServerError,
-            501 => ResponseCode::NotImplemented,
-            502 => ResponseCode::BadGateway,
-            503 => ResponseCode::ServiceUnavailable,
-            504 => ResponseCode::GatewayTimeout,
-            505 => ResponseCode::HttpVersionNotSupported,
-            506 => ResponseCode::VariantAlsoNegotiates,
-            507 => ResponseCode::InsufficientStorage,
-            508 => ResponseCode::LoopDetected,
-            510 => ResponseCode::NotExtended,
-            511 => ResponseCode::NetworkAuthenticationRequired,
-            _ => ResponseCode::Undefined,
+        if i < 100 || i > 999 {
+            return ResponseCode::Undefined;
+        }
+        KNOWN_CODES
+            .iter()
+            .find(|k| u32::from(k.0) == i)
+            .map(|k| k.1.clone())
+            .unwrap_or(ResponseCode::Other(i as u16))
+    }
+
+    /// Returns the numeric status code, 0 when undefined
+    pub fn as_u16(&self) -> u16 {
+        match *self {
+            ResponseCode::Undefined => 0,
+            ResponseCode::Other(code) => code,
+            ref named => KNOWN_CODES
+                .iter()
+                .find(|k| k.1 == *named)
+                .map(|k| k.0)
+                .unwrap_or(0),
+        }
+    }
+
+    /// Returns true for a 1xx status
+    pub fn is_informational(&self) -> bool {
+        self.as_u16() / 100 == 1
+    }
+
+    /// Returns true for a 2xx status
+    pub fn is_success(&self) -> bool {
+        self.as_u16() / 100 == 2
+    }
+
+    /// Returns true for a 3xx status
+    pub fn is_redirect(&self) -> bool {
+        self.as_u16() / 100 == 3
+    }
+
+    /// Returns true for a 4xx status
+    pub fn is_client_error(&self) -> bool {
+        self.as_u16() / 100 == 4
+    }
+
+    /// Returns true for a 5xx status
+    pub fn is_server_error(&self) -> bool {
+        self.as_u16() / 100 == 5
+    }
+}
+
+/// The protocol version of a response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HttpVersion::Http10 => write!(f, "HTTP/1.0"),
+            HttpVersion::Http11 => write!(f, "HTTP/1.1"),
+        }
+    }
+}
+
+impl FromStr for HttpVersion {
+    type Err = Error;
+
+    /// Parses the version of a status line. A later 1.x version is read as 1.1,
+    /// which it is compatible with (RFC 9110 section 2.5)
+    fn from_str(s: &str) -> Result<HttpVersion, Error> {
+        let bytes = s.as_bytes();
+        match s {
+            "HTTP/1.0" => Ok(HttpVersion::Http10),
+            _ if bytes.len() == 8 && s.starts_with("HTTP/1.") && (bytes[7] as char).is_digit(10) => {
+                Ok(HttpVersion::Http11)
+            }
+            _ => Err(Error::Parse(format!("Unsupported http version: {}", s))),
         }
     }
 }
@@ -186,8 +349,43 @@ pub fn methods_get() {
     assert_eq!(s, "GET".to_owned());
 }
 
+#[test]
+pub fn methods_extension() {
+    let m: HttpMethods = "PROPFIND".parse().unwrap();
+    assert_eq!(m.to_string(), "PROPFIND".to_owned());
+    let m: HttpMethods = "DELETE".parse().unwrap();
+    assert_eq!(m, HttpMethods::Delete);
+    assert!("".parse::<HttpMethods>().is_err());
+    assert!("GET /".parse::<HttpMethods>().is_err());
+}
+
 #[test]
 pub fn response_code_success() {
     let s = ResponseCode::Continue.to_string();
     assert_eq!(s, "CONTINUE");
 }
+
+#[test]
+pub fn response_code_keeps_unknown() {
+    assert_eq!(ResponseCode::from_int(404), ResponseCode::NotFound);
+    assert_eq!(ResponseCode::from_int(299), ResponseCode::Other(299));
+    assert_eq!(ResponseCode::from_int(42), ResponseCode::Undefined);
+    assert_eq!(ResponseCode::from_int(420).as_u16(), 420);
+    assert_eq!(ResponseCode::NetworkAuthenticationRequired.as_u16(), 511);
+    assert_eq!(ResponseCode::Other(599).to_string(), "599");
+    assert!(ResponseCode::Other(299).is_success());
+    assert!(ResponseCode::Found.is_redirect());
+    assert!(ResponseCode::from_int(420).is_client_error());
+    assert!(ResponseCode::Other(599).is_server_error());
+    assert!(ResponseCode::Continue.is_informational());
+    assert!(!ResponseCode::Undefined.is_success());
+}
+
+#[test]
+pub fn http_versions() {
+    assert_eq!("HTTP/1.0".parse::<HttpVersion>().unwrap(), HttpVersion::Http10);
+    assert_eq!("HTTP/1.1".parse::<HttpVersion>().unwrap(), HttpVersion::Http11);
+    assert_eq!("HTTP/1.2".parse::<HttpVersion>().unwrap(), HttpVersion::Http11);
+    assert!("HTTP/2.0".parse::<HttpVersion>().is_err());
+    assert!("ICY".parse::<HttpVersion>().is_err());
+}
@@ -0,0 +1,471 @@
+// Just enough DER to find the parts of an X.509 certificate that hasty looks at
+
+use std::net::IpAddr;
+use std::str;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const UTF8_STRING: u8 = 0x0c;
+const PRINTABLE_STRING: u8 = 0x13;
+const T61_STRING: u8 = 0x14;
+const IA5_STRING: u8 = 0x16;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const BMP_STRING: u8 = 0x1e;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const EXPLICIT_0: u8 = 0xa0;
+const EXTENSIONS: u8 = 0xa3;
+const SAN_DNS_NAME: u8 = 0x82;
+const SAN_IP_ADDRESS: u8 = 0x87;
+
+// 2.5.29.17
+const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// One element: its tag, its contents and the whole encoding including the header
+pub struct Element<'a> {
+    pub tag: u8,
+    pub contents: &'a [u8],
+    pub encoded: &'a [u8],
+}
+
+/// Reads the element at the start of `data`, returning it and the input that follows
+pub fn read_element<'a>(data: &'a [u8]) -> Option<(Element<'a>, &'a [u8])> {
+    let tag = *data.get(0)?;
+    let first = *data.get(1)? as usize;
+    let (length, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let mut length = 0usize;
+        for byte in data.get(2..2 + count)? {
+            length = (length << 8) | *byte as usize;
+        }
+        (length, 2 + count)
+    };
+    let end = header.checked_add(length)?;
+    if end > data.len() {
+        return None;
+    }
+    let element = Element {
+        tag: tag,
+        contents: &data[header..end],
+        encoded: &data[..end],
+    };
+    Some((element, &data[end..]))
+}
+
+/// Reads an element with the expected tag
+fn expect<'a>(data: &'a [u8], tag: u8) -> Option<(Element<'a>, &'a [u8])> {
+    let (element, rest) = read_element(data)?;
+    if element.tag == tag {
+        Some((element, rest))
+    } else {
+        None
+    }
+}
+
+/// Returns the fields of the tbsCertificate, after the optional version
+fn tbs_fields(cert: &[u8]) -> Option<&[u8]> {
+    let (cert, _) = expect(cert, SEQUENCE)?;
+    let (tbs, _) = expect(cert.contents, SEQUENCE)?;
+    match read_element(tbs.contents)? {
+        (ref version, rest) if version.tag == EXPLICIT_0 => Some(rest),
+        _ => Some(tbs.contents),
+    }
+}
+
+/// The parts of a tbsCertificate that hasty reads, as the contents of each field
+pub struct Tbs<'a> {
+    pub serial: &'a [u8],
+    pub issuer: &'a [u8],
+    pub validity: &'a [u8],
+    pub subject: &'a [u8],
+    // the fields from the subjectPublicKeyInfo on
+    rest: &'a [u8],
+}
+
+pub fn tbs<'a>(cert: &'a [u8]) -> Option<Tbs<'a>> {
+    let (serial, rest) = expect(tbs_fields(cert)?, INTEGER)?;
+    let (_signature, rest) = expect(rest, SEQUENCE)?;
+    let (issuer, rest) = expect(rest, SEQUENCE)?;
+    let (validity, rest) = expect(rest, SEQUENCE)?;
+    let (subject, rest) = expect(rest, SEQUENCE)?;
+    Some(Tbs {
+        serial: serial.contents,
+        issuer: issuer.contents,
+        validity: validity.contents,
+        subject: subject.contents,
+        rest: rest,
+    })
+}
+
+fn from_public_key(cert: &[u8]) -> Option<&[u8]> {
+    tbs(cert).map(|t| t.rest)
+}
+
+/// Returns the subject of a DER certificate, formatted like `C=AU, O=Example, CN=localhost`
+pub fn subject(cert: &[u8]) -> Option<String> {
+    name_to_string(tbs(cert)?.subject)
+}
+
+/// Returns the issuer of a DER certificate, formatted like the subject
+pub fn issuer(cert: &[u8]) -> Option<String> {
+    name_to_string(tbs(cert)?.issuer)
+}
+
+/// Returns the notBefore and notAfter times of a DER certificate
+pub fn validity(cert: &[u8]) -> Option<(SystemTime, SystemTime)> {
+    let (not_before, rest) = read_element(tbs(cert)?.validity)?;
+    let (not_after, _) = read_element(rest)?;
+    Some((parse_time(&not_before)?, parse_time(&not_after)?))
+}
+
+/// The signed parts shared by certificates and revocation lists
+pub struct Signed<'a> {
+    /// The encoded tbsCertificate or tbsCertList, which the signature covers
+    pub data: &'a [u8],
+    /// The object identifier of the signature algorithm
+    pub algorithm: &'a [u8],
+    pub signature: &'a [u8],
+}
+
+/// Reads the signature of a DER certificate or revocation list (RFC 5280 section 4.1.1)
+pub fn signed<'a>(der: &'a [u8]) -> Option<Signed<'a>> {
+    let (outer, _) = expect(der, SEQUENCE)?;
+    let (tbs, rest) = expect(outer.contents, SEQUENCE)?;
+    let (algorithm, rest) = expect(rest, SEQUENCE)?;
+    let (oid, _) = expect(algorithm.contents, OID)?;
+    let (signature, _) = expect(rest, BIT_STRING)?;
+    Some(Signed {
+        data: tbs.encoded,
+        algorithm: oid.contents,
+        signature: bit_string(&signature)?,
+    })
+}
+
+/// The parts of a CertificateList that revocation checks read
+pub struct Crl<'a> {
+    /// The contents of the issuer Name, comparable with `Tbs::issuer`
+    pub issuer: &'a [u8],
+    pub next_update: Option<SystemTime>,
+    /// The serial numbers of the revoked certificates
+    pub revoked: Vec<&'a [u8]>,
+    pub signed: Signed<'a>,
+}
+
+/// Reads a DER certificate revocation list (RFC 5280 section 5.1)
+pub fn crl<'a>(der: &'a [u8]) -> Option<Crl<'a>> {
+    let (list, _) = expect(der, SEQUENCE)?;
+    let (tbs, _) = expect(list.contents, SEQUENCE)?;
+    let rest = match read_element(tbs.contents)? {
+        (ref version, rest) if version.tag == INTEGER => rest,
+        _ => tbs.contents,
+    };
+    let (_signature, rest) = expect(rest, SEQUENCE)?;
+    let (issuer, rest) = expect(rest, SEQUENCE)?;
+    let (this_update, mut rest) = read_element(rest)?;
+    parse_time(&this_update)?;
+    let mut next_update = None;
+    if let Some((time, next)) = read_element(rest) {
+        if time.tag == UTC_TIME || time.tag == GENERALIZED_TIME {
+            next_update = Some(parse_time(&time)?);
+            rest = next;
+        }
+    }
+    // without revoked certificates the list goes straight on to its extensions
+    let mut revoked = Vec::new();
+    if let Some((entries, _)) = expect(rest, SEQUENCE) {
+        let mut entries = entries.contents;
+        while let Some((entry, next)) = read_element(entries) {
+            entries = next;
+            let (serial, _) = expect(entry.contents, INTEGER)?;
+            revoked.push(serial.contents);
+        }
+    }
+    Some(Crl {
+        issuer: issuer.contents,
+        next_update: next_update,
+        revoked: revoked,
+        signed: signed(der)?,
+    })
+}
+
+/// Returns the contents of a BIT STRING made of whole bytes
+fn bit_string<'a>(element: &Element<'a>) -> Option<&'a [u8]> {
+    match element.contents.split_first() {
+        Some((&0, bits)) => Some(bits),
+        _ => None,
+    }
+}
+
+/// Formats the attributes of a Name in the order they are encoded
+fn name_to_string(name: &[u8]) -> Option<String> {
+    let mut parts = Vec::new();
+    let mut rdns = name;
+    while let Some((rdn, next)) = read_element(rdns) {
+        rdns = next;
+        if rdn.tag != SET {
+            return None;
+        }
+        let mut attributes = rdn.contents;
+        while let Some((attribute, next)) = read_element(attributes) {
+            attributes = next;
+            let (oid, rest) = expect(attribute.contents, OID)?;
+            let (value, _) = read_element(rest)?;
+            parts.push(format!("{}={}", attribute_name(oid.contents), string_value(&value)));
+        }
+    }
+    Some(parts.join(", "))
+}
+
+fn attribute_name(oid: &[u8]) -> String {
+    match oid {
+        [0x55, 0x04, 0x03] => "CN".to_owned(),
+        [0x55, 0x04, 0x05] => "serialNumber".to_owned(),
+        [0x55, 0x04, 0x06] => "C".to_owned(),
+        [0x55, 0x04, 0x07] => "L".to_owned(),
+        [0x55, 0x04, 0x08] => "ST".to_owned(),
+        [0x55, 0x04, 0x09] => "street".to_owned(),
+        [0x55, 0x04, 0x0a] => "O".to_owned(),
+        [0x55, 0x04, 0x0b] => "OU".to_owned(),
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress".to_owned(),
+        _ => oid_to_string(oid),
+    }
+}
+
+/// Returns the dotted form of an object identifier
+fn oid_to_string(oid: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut value: u64 = 0;
+    for byte in oid {
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 != 0 {
+            continue;
+        }
+        // the first subidentifier holds the first two arcs, X * 40 + Y, where only
+        // arc 2 can have a second arc of 40 or more
+        if parts.is_empty() {
+            let first = if value < 80 { value / 40 } else { 2 };
+            parts.push(first.to_string());
+            parts.push((value - first * 40).to_string());
+        } else {
+            parts.push(value.to_string());
+        }
+        value = 0;
+    }
+    parts.join(".")
+}
+
+fn string_value(value: &Element) -> String {
+    match value.tag {
+        UTF8_STRING | PRINTABLE_STRING | IA5_STRING | T61_STRING => {
+            String::from_utf8_lossy(value.contents).into_owned()
+        }
+        BMP_STRING => {
+            let units: Vec<u16> = value
+                .contents
+                .chunks(2)
+                .map(|c| (u16::from(c[0]) << 8) | u16::from(*c.get(1).unwrap_or(&0)))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => value
+            .contents
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<String>>()
+            .join(""),
+    }
+}
+
+/// Parses a UTCTime or GeneralizedTime in the `Z` form certificates use (RFC 5280 section 4.1.2.5)
+fn parse_time(time: &Element) -> Option<SystemTime> {
+    let text = str::from_utf8(time.contents).ok()?;
+    let (year, rest) = match time.tag {
+        UTC_TIME => {
+            let year: i64 = text.get(0..2)?.parse().ok()?;
+            (if year >= 50 { 1900 + year } else { 2000 + year }, text.get(2..)?)
+        }
+        GENERALIZED_TIME => (text.get(0..4)?.parse().ok()?, text.get(4..)?),
+        _ => return None,
+    };
+    if rest.len() != 11 || !rest.ends_with('Z') {
+        return None;
+    }
+    let field = |i: usize| rest.get(i..i + 2).and_then(|s| s.parse::<i64>().ok());
+    let days = days_from_civil(year, field(0)?, field(2)?);
+    let seconds = days * 86400 + field(4)? * 3600 + field(6)? * 60 + field(8)?;
+    if seconds >= 0 {
+        Some(UNIX_EPOCH + Duration::from_secs(seconds as u64))
+    } else {
+        Some(UNIX_EPOCH - Duration::from_secs(-seconds as u64))
+    }
+}
+
+/// Returns the days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the encoded subjectPublicKeyInfo of a DER certificate (RFC 5280 section 4.1)
+pub fn subject_public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    let (spki, _) = expect(from_public_key(cert)?, SEQUENCE)?;
+    Some(spki.encoded)
+}
+
+/// The parts of a subjectPublicKeyInfo that signatures are checked with
+pub struct PublicKey<'a> {
+    /// The object identifier of the key algorithm
+    pub algorithm: &'a [u8],
+    /// The object identifier parameter of the algorithm, like the curve of an EC key
+    pub parameter: Option<&'a [u8]>,
+    pub key: &'a [u8],
+}
+
+/// Returns the contents of the subjectPublicKeyInfo of a DER certificate, as trust anchors keep it
+pub fn spki_contents(cert: &[u8]) -> Option<&[u8]> {
+    read_element(subject_public_key_info(cert)?).map(|(spki, _)| spki.contents)
+}
+
+/// Reads the contents of a subjectPublicKeyInfo
+pub fn public_key<'a>(spki: &'a [u8]) -> Option<PublicKey<'a>> {
+    let (algorithm, rest) = expect(spki, SEQUENCE)?;
+    let (oid, parameters) = expect(algorithm.contents, OID)?;
+    let (key, _) = expect(rest, BIT_STRING)?;
+    Some(PublicKey {
+        algorithm: oid.contents,
+        parameter: expect(parameters, OID).map(|(p, _)| p.contents),
+        key: bit_string(&key)?,
+    })
+}
+
+/// Returns the value of the extension with the given object identifier
+fn extension<'a>(cert: &'a [u8], oid: &[u8]) -> Option<&'a [u8]> {
+    let (_spki, mut rest) = expect(from_public_key(cert)?, SEQUENCE)?;
+    // the unique identifiers may come before the extensions
+    while let Some((element, next)) = read_element(rest) {
+        rest = next;
+        if element.tag != EXTENSIONS {
+            continue;
+        }
+        let (list, _) = expect(element.contents, SEQUENCE)?;
+        let mut extensions = list.contents;
+        while let Some((extension, next)) = read_element(extensions) {
+            extensions = next;
+            let (id, fields) = expect(extension.contents, OID)?;
+            if id.contents != oid {
+                continue;
+            }
+            let fields = match read_element(fields)? {
+                (ref critical, rest) if critical.tag == BOOLEAN => rest,
+                _ => fields,
+            };
+            let (value, _) = expect(fields, OCTET_STRING)?;
+            return Some(value.contents);
+        }
+    }
+    None
+}
+
+/// Returns the contents of the subjectAltName entries with the given tag (RFC 5280 section 4.2.1.6)
+fn alt_names(cert: &[u8], tag: u8) -> Vec<&[u8]> {
+    let mut found = Vec::new();
+    let names = match extension(cert, SUBJECT_ALT_NAME).and_then(|v| expect(v, SEQUENCE)) {
+        Some((names, _)) => names.contents,
+        None => return found,
+    };
+    let mut rest = names;
+    while let Some((name, next)) = read_element(rest) {
+        rest = next;
+        if name.tag == tag {
+            found.push(name.contents);
+        }
+    }
+    found
+}
+
+/// Returns the dNSName entries of the subjectAltName extension
+pub fn dns_names(cert: &[u8]) -> Vec<String> {
+    alt_names(cert, SAN_DNS_NAME)
+        .iter()
+        .filter_map(|name| str::from_utf8(name).ok())
+        .map(|name| name.to_owned())
+        .collect()
+}
+
+/// Returns the iPAddress entries of the subjectAltName extension
+pub fn ip_addresses(cert: &[u8]) -> Vec<IpAddr> {
+    let mut addresses = Vec::new();
+    for bytes in alt_names(cert, SAN_IP_ADDRESS) {
+        if bytes.len() == 4 {
+            let mut octets = [0u8; 4];
+            octets.copy_from_slice(bytes);
+            addresses.push(IpAddr::from(octets));
+        } else if bytes.len() == 16 {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(bytes);
+            addresses.push(IpAddr::from(octets));
+        }
+    }
+    addresses
+}
+
+/// Returns the DER of a PEM file holding a single certificate, for tests
+#[cfg(test)]
+pub fn pem_to_der(pem: &[u8]) -> Vec<u8> {
+    extern crate base64;
+
+    let pem = String::from_utf8_lossy(pem);
+    let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+    base64::decode(&body).unwrap()
+}
+
+#[test]
+fn der_long_lengths() {
+    let mut data = vec![0x04, 0x82, 0x01, 0x00];
+    data.extend(vec![7u8; 256]);
+    data.push(0x05);
+    let (element, rest) = read_element(&data).unwrap();
+    assert_eq!(element.tag, 0x04);
+    assert_eq!(element.contents.len(), 256);
+    assert_eq!(rest, &[0x05]);
+    assert!(read_element(&[0x04, 0x82, 0x01]).is_none());
+    assert!(read_element(&[0x04, 0x05, 0x00]).is_none());
+}
+
+#[test]
+fn der_ip_addresses() {
+    let cert = pem_to_der(include_bytes!("../test_server/ip.pem"));
+    let addresses: Vec<String> = ip_addresses(&cert).iter().map(|ip| ip.to_string()).collect();
+    assert_eq!(addresses, vec!["127.0.0.1", "::1"]);
+    assert!(dns_names(&cert).is_empty());
+    assert!(subject_public_key_info(&cert).is_some());
+}
+
+#[test]
+fn der_names_and_validity() {
+    let cert = pem_to_der(include_bytes!("../test_server/https.pem"));
+    let name = "C=AU, ST=Some-State, O=Internet Widgits Pty Ltd, CN=localhost";
+    assert_eq!(subject(&cert), Some(name.to_owned()));
+    assert_eq!(issuer(&cert), Some(name.to_owned()));
+    let (_, not_after) = validity(&cert).unwrap();
+    // Jun 15 20:37:24 2020 GMT
+    assert_eq!(not_after, UNIX_EPOCH + Duration::from_secs(1592253444));
+    assert_eq!(oid_to_string(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d]), "1.2.840.113549");
+    assert_eq!(oid_to_string(&[0x55, 0x1d, 0x11]), "2.5.29.17");
+    assert_eq!(oid_to_string(&[0x88, 0x37, 0x03]), "2.999.3");
+    assert_eq!(oid_to_string(&[0x00]), "0.0");
+}
@@ -0,0 +1,142 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{Error as IoError, ErrorKind};
+use std::result::Result as StdResult;
+
+use pinning::PIN_MISMATCH;
+use revocation::{CERTIFICATE_REVOKED, REVOCATION_UNAVAILABLE};
+use rustls::TLSError;
+
+/// Errors returned by hasty
+#[derive(Debug)]
+pub enum Error {
+    /// The url could not be parsed, or has no host
+    InvalidUrl(String),
+    /// The url scheme is not http or https
+    UnsupportedScheme(String),
+    /// The tcp connection to the remote host could not be established
+    Connect(IoError),
+    /// The tls session failed
+    Tls(TLSError),
+    /// Reading from or writing to the connection failed
+    Io(IoError),
+    /// The data received was not a valid http response
+    Parse(String),
+    /// The operation did not complete in time
+    Timeout,
+    /// The redirect policy's limit on hops was reached
+    TooManyRedirects(usize),
+    /// The proxy did not open a tunnel to the remote host
+    Proxy(String),
+    /// A certificate or trust anchor could not be loaded
+    Certificate(String),
+    /// The host presented none of the certificates or keys pinned for it
+    PinMismatch(String),
+    /// A certificate presented by the host is listed as revoked
+    CertificateRevoked(String),
+    /// No current revocation list signed by its issuer covers a certificate, in hard fail mode
+    RevocationUnavailable(String),
+    /// The configuration combines settings that cannot be used together
+    Config(String),
+}
+
+pub type Result<T> = StdResult<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidUrl(ref u) => write!(f, "Invalid url: {}", u),
+            Error::UnsupportedScheme(ref s) => write!(f, "Unsupported protocol: {}", s),
+            Error::Connect(ref e) => write!(f, "Unable to connect: {}", e),
+            Error::Tls(ref e) => write!(f, "TLS error: {}", e),
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Parse(ref m) => write!(f, "Could not parse http response: {}", m),
+            Error::Timeout => write!(f, "Operation timed out"),
+            Error::TooManyRedirects(max) => write!(f, "More than {} redirects", max),
+            Error::Proxy(ref m) => write!(f, "Proxy error: {}", m),
+            Error::Certificate(ref m) => write!(f, "Certificate error: {}", m),
+            Error::PinMismatch(ref host) => write!(f, "No certificate pin matched for {}", host),
+            Error::CertificateRevoked(ref subject) => write!(f, "Certificate revoked: {}", subject),
+            Error::RevocationUnavailable(ref subject) => {
+                write!(f, "Revocation status unavailable: {}", subject)
+            }
+            Error::Config(ref m) => write!(f, "Invalid configuration: {}", m),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            Error::Connect(ref e) => Some(e),
+            Error::Tls(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<IoError> for Error {
+    /// Unwraps hasty and rustls errors that were passed through an `io::Read`
+    fn from(e: IoError) -> Error {
+        let wrapped = match e.get_ref() {
+            Some(inner) => inner.is::<Error>() || inner.is::<TLSError>(),
+            None => false,
+        };
+        if !wrapped {
+            return Error::Io(e);
+        }
+        let inner = e.into_inner().unwrap();
+        match inner.downcast::<Error>() {
+            Ok(err) => *err,
+            Err(inner) => match inner.downcast::<TLSError>() {
+                Ok(err) => Error::from(*err),
+                Err(inner) => Error::Io(IoError::new(ErrorKind::Other, inner)),
+            },
+        }
+    }
+}
+
+impl From<Error> for IoError {
+    fn from(e: Error) -> IoError {
+        match e {
+            Error::Io(e) => e,
+            Error::Timeout => IoError::new(ErrorKind::TimedOut, e),
+            e => IoError::new(ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+impl From<TLSError> for Error {
+    /// Picks out the pin mismatches and revocations reported by hasty's verifiers
+    fn from(e: TLSError) -> Error {
+        match e {
+            TLSError::General(ref m) if m.starts_with(PIN_MISMATCH) => {
+                Error::PinMismatch(m[PIN_MISMATCH.len()..].to_owned())
+            }
+            TLSError::General(ref m) if m.starts_with(CERTIFICATE_REVOKED) => {
+                Error::CertificateRevoked(m[CERTIFICATE_REVOKED.len()..].to_owned())
+            }
+            TLSError::General(ref m) if m.starts_with(REVOCATION_UNAVAILABLE) => {
+                Error::RevocationUnavailable(m[REVOCATION_UNAVAILABLE.len()..].to_owned())
+            }
+            e => Error::Tls(e),
+        }
+    }
+}
+
+#[test]
+fn error_io_source() {
+    let err = Error::from(IoError::new(ErrorKind::ConnectionReset, "reset"));
+    assert!(err.source().is_some());
+    assert!(Error::Timeout.source().is_none());
+}
+
+#[test]
+fn error_round_trips_through_io() {
+    let io: IoError = Error::Parse("bad chunk".to_owned()).into();
+    match Error::from(io) {
+        Error::Parse(m) => assert_eq!(m, "bad chunk"),
+        e => panic!("unexpected error: {}", e),
+    }
+}
@@ -0,0 +1,98 @@
+/// Header fields, kept in the order they were added.
+///
+/// Names are matched case-insensitively and a name can have several values,
+/// like the `Set-Cookie` fields of a response.
+///
+/// # Example
+///
+/// ```
+/// use hasty::HeaderMap;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.append("Set-Cookie", "a=1");
+/// headers.append("set-cookie", "b=2");
+/// assert_eq!(headers.get("SET-COOKIE"), Some("a=1"));
+/// assert_eq!(headers.get_all("Set-Cookie"), vec!["a=1", "b=2"]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    fields: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    /// Returns an empty map
+    pub fn new() -> HeaderMap {
+        HeaderMap { fields: Vec::new() }
+    }
+
+    /// Adds a value for `name`, after any values it already has
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.fields.push((name.to_owned(), value.to_owned()));
+    }
+
+    /// Replaces every value of `name` with `value`
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.remove(name);
+        self.append(name, value);
+    }
+
+    /// Returns the first value of `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|f| f.0.eq_ignore_ascii_case(name))
+            .map(|f| f.1.as_str())
+    }
+
+    /// Returns every value of `name`, in the order they were added
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.fields
+            .iter()
+            .filter(|f| f.0.eq_ignore_ascii_case(name))
+            .map(|f| f.1.as_str())
+            .collect()
+    }
+
+    /// Returns whether `name` has any value
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Removes every value of `name`
+    pub fn remove(&mut self, name: &str) {
+        self.fields.retain(|f| !f.0.eq_ignore_ascii_case(name));
+    }
+
+    /// Returns the number of fields, counting each value of a name
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Returns the fields as name and value pairs, in the order they were added
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        self.fields.iter().map(|f| (f.0.as_str(), f.1.as_str()))
+    }
+}
+
+#[test]
+fn header_map_order_and_case() {
+    let mut headers = HeaderMap::new();
+    headers.append("Content-Type", "text/plain");
+    headers.append("Set-Cookie", "a=1");
+    headers.append("set-cookie", "b=2");
+    assert_eq!(headers.get("content-type"), Some("text/plain"));
+    assert_eq!(headers.get_all("SET-COOKIE"), vec!["a=1", "b=2"]);
+    assert_eq!(headers.len(), 3);
+
+    headers.insert("set-cookie", "c=3");
+    let fields: Vec<(&str, &str)> = headers.iter().collect();
+    assert_eq!(fields, vec![("Content-Type", "text/plain"), ("set-cookie", "c=3")]);
+
+    headers.remove("CONTENT-TYPE");
+    assert!(!headers.contains("content-type"));
+    assert_eq!(headers.len(), 1);
+}
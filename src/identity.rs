@@ -0,0 +1,104 @@
+extern crate p12;
+
+use std::io::Cursor;
+
+use rustls::internal::pemfile;
+use rustls::sign::RSASigningKey;
+use rustls::{Certificate, PrivateKey};
+
+use error::Error;
+
+/// A client certificate chain and its private key, presented to servers that ask for one
+#[derive(Clone)]
+pub struct ClientIdentity {
+    certs: Vec<Certificate>,
+    key: PrivateKey,
+}
+
+impl ClientIdentity {
+    /// Returns an identity from a PEM certificate chain, starting with the client
+    /// certificate, and a PEM RSA private key in PKCS#1 or PKCS#8 form
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::{ClientIdentity, Config};
+    ///
+    /// let cert = include_bytes!("../test_server/https.pem");
+    /// let key = include_bytes!("../test_server/https.key");
+    /// let identity = ClientIdentity::from_pem(cert, key).unwrap();
+    /// let config = Config::new().client_identity(identity);
+    /// ```
+    pub fn from_pem(cert_chain: &[u8], key: &[u8]) -> Result<ClientIdentity, Error> {
+        let certs = pemfile::certs(&mut Cursor::new(cert_chain))
+            .map_err(|_| Error::Certificate("Invalid PEM certificate chain".to_owned()))?;
+        let mut keys = pemfile::pkcs8_private_keys(&mut Cursor::new(key)).unwrap_or_default();
+        if keys.is_empty() {
+            keys = pemfile::rsa_private_keys(&mut Cursor::new(key)).unwrap_or_default();
+        }
+        let key = keys
+            .into_iter()
+            .next()
+            .ok_or(Error::Certificate("No private key found in PEM data".to_owned()))?;
+        ClientIdentity::new(certs, key)
+    }
+
+    /// Returns an identity from a PKCS#12 archive, such as a `.pfx` file
+    pub fn from_pkcs12(der: &[u8], password: &str) -> Result<ClientIdentity, Error> {
+        let pfx = p12::PFX::parse(der)
+            .map_err(|e| Error::Certificate(format!("Invalid PKCS#12 archive: {:?}", e)))?;
+        if !pfx.verify_mac(password) {
+            return Err(Error::Certificate("Wrong PKCS#12 password".to_owned()));
+        }
+        let certs = pfx
+            .cert_bags(password)
+            .map_err(|e| Error::Certificate(format!("Unable to read PKCS#12 certificates: {:?}", e)))?;
+        let key = pfx
+            .key_bags(password)
+            .map_err(|e| Error::Certificate(format!("Unable to read PKCS#12 key: {:?}", e)))?
+            .into_iter()
+            .next()
+            .ok_or(Error::Certificate("No private key found in PKCS#12 archive".to_owned()))?;
+        ClientIdentity::new(certs.into_iter().map(Certificate).collect(), PrivateKey(key))
+    }
+
+    fn new(certs: Vec<Certificate>, key: PrivateKey) -> Result<ClientIdentity, Error> {
+        if certs.is_empty() {
+            return Err(Error::Certificate("No client certificate found".to_owned()));
+        }
+        // rustls panics on a key it can not sign with, so check it up front
+        RSASigningKey::new(&key)
+            .map_err(|_| Error::Certificate("Unsupported private key, it must be RSA".to_owned()))?;
+        Ok(ClientIdentity {
+            certs: certs,
+            key: key,
+        })
+    }
+
+    /// Returns the certificate chain, starting with the client certificate
+    pub fn certs(&self) -> Vec<Certificate> {
+        self.certs.clone()
+    }
+
+    /// Returns the private key
+    pub fn key(&self) -> PrivateKey {
+        self.key.clone()
+    }
+}
+
+#[test]
+fn identity_from_pem() {
+    let cert = include_bytes!("../test_server/https.pem");
+    let key = include_bytes!("../test_server/https.key");
+    let identity = ClientIdentity::from_pem(cert, key).unwrap();
+    assert_eq!(identity.certs().len(), 1);
+    assert!(ClientIdentity::from_pem(cert, b"not a key").is_err());
+}
+
+#[test]
+fn identity_from_pkcs12() {
+    let pfx = include_bytes!("../test_server/https.pfx");
+    let identity = ClientIdentity::from_pkcs12(pfx, "").unwrap();
+    assert_eq!(identity.certs().len(), 1);
+    assert!(ClientIdentity::from_pkcs12(pfx, "wrong").is_err());
+}
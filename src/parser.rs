@@ -0,0 +1,639 @@
+use std::mem;
+
+use constants::{HttpMethods, HttpVersion};
+use error::Error;
+
+// the longest status, header, chunk size or trailer line accepted
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+// the largest header or trailer block, and the most fields in one
+const MAX_BLOCK_LENGTH: usize = 256 * 1024;
+const MAX_FIELDS: usize = 256;
+// the most interim 1xx responses accepted before the final one
+const MAX_INTERIM: usize = 32;
+
+/// How the end of a response body is found (RFC 9112 section 6.3)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BodyLength {
+    Empty,
+    Fixed(usize),
+    Chunked,
+    UntilClose,
+}
+
+/// A part of a response, reported as soon as its bytes have been pushed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseEvent {
+    /// A status line. Interim 1xx responses come before the final one, each with its own headers
+    Status {
+        version: String,
+        code: u16,
+        reason: String,
+    },
+    /// A header field of the latest status line
+    Header { name: String, value: String },
+    /// The end of a header block, and how the body that follows it is delimited
+    HeadersEnd(BodyLength),
+    /// Body bytes, with any chunked framing removed
+    Body(Vec<u8>),
+    /// A trailer field sent after a chunked body
+    Trailer { name: String, value: String },
+    /// The end of the final response
+    End,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum State {
+    StatusLine,
+    Headers,
+    Fixed(usize),
+    ChunkSize,
+    ChunkData(usize),
+    ChunkDataEnd,
+    Trailers,
+    UntilClose,
+    Done,
+}
+
+/// A push based parser for a single http response.
+///
+/// It does no I/O of its own: bytes are pushed in slices split anywhere, as they
+/// arrive from a connection or are read from a capture, and the parts of the
+/// response are reported as `ParseEvent`s.
+///
+/// # Example
+///
+/// ```
+/// use hasty::{HttpMethods, ParseEvent, ResponseParser};
+///
+/// let capture = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+/// let mut parser = ResponseParser::new(HttpMethods::Get);
+/// let mut events = Vec::new();
+/// for piece in capture.chunks(4) {
+///     parser.push(piece, &mut events).unwrap();
+/// }
+/// assert!(parser.is_complete());
+/// let body: Vec<u8> = events
+///     .into_iter()
+///     .flat_map(|e| match e {
+///         ParseEvent::Body(data) => data,
+///         _ => Vec::new(),
+///     })
+///     .collect();
+/// assert_eq!(&body[..], b"hello");
+/// ```
+pub struct ResponseParser {
+    method: HttpMethods,
+    state: State,
+    line: Vec<u8>,
+    code: u16,
+    header_block: Vec<u8>,
+    // the size and field count of the header or trailer block being read
+    block_length: usize,
+    fields: usize,
+    interim: usize,
+    head: Option<Vec<u8>>,
+    // a header that may still continue on an obsolete folded line
+    pending: Option<(String, String)>,
+    chunked: Option<bool>,
+    content_length: Option<usize>,
+    body_length: Option<BodyLength>,
+}
+
+impl ResponseParser {
+    /// Returns a parser for the response to a request made with `method`, which
+    /// decides whether the response has a body
+    pub fn new(method: HttpMethods) -> ResponseParser {
+        ResponseParser {
+            method: method,
+            state: State::StatusLine,
+            line: Vec::new(),
+            code: 0,
+            header_block: Vec::new(),
+            block_length: 0,
+            fields: 0,
+            interim: 0,
+            head: None,
+            pending: None,
+            chunked: None,
+            content_length: None,
+            body_length: None,
+        }
+    }
+
+    /// Returns true once the whole response has been seen
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Returns how the body is delimited, once the headers have been seen
+    pub fn body_length(&self) -> Option<BodyLength> {
+        self.body_length.clone()
+    }
+
+    /// Returns the status line and header block of the final response, once seen
+    pub fn head(&self) -> Option<&[u8]> {
+        self.head.as_ref().map(|h| &h[..])
+    }
+
+    /// Consumes bytes of the response, appending what they complete to `events`,
+    /// and returns how many bytes belong to the response.
+    ///
+    /// Bytes past the end of the response are left unconsumed.
+    pub fn push(&mut self, data: &[u8], events: &mut Vec<ParseEvent>) -> Result<usize, Error> {
+        let mut used = 0;
+        while used < data.len() {
+            let rest = &data[used..];
+            match self.state {
+                State::Done => break,
+                State::UntilClose => {
+                    events.push(ParseEvent::Body(rest.to_vec()));
+                    used = data.len();
+                }
+                State::Fixed(remaining) => {
+                    let n = usize::min(remaining, rest.len());
+                    events.push(ParseEvent::Body(rest[..n].to_vec()));
+                    used += n;
+                    if remaining == n {
+                        self.end(events);
+                    } else {
+                        self.state = State::Fixed(remaining - n);
+                    }
+                }
+                State::ChunkData(remaining) => {
+                    let n = usize::min(remaining, rest.len());
+                    events.push(ParseEvent::Body(rest[..n].to_vec()));
+                    used += n;
+                    self.state = if remaining == n {
+                        State::ChunkDataEnd
+                    } else {
+                        State::ChunkData(remaining - n)
+                    };
+                }
+                _ => match rest.iter().position(|&b| b == b'\n') {
+                    Some(i) if self.line.len() + i >= MAX_LINE_LENGTH => {
+                        return Err(Error::Parse("Response line too long".to_owned()))
+                    }
+                    None if self.line.len() + rest.len() >= MAX_LINE_LENGTH => {
+                        return Err(Error::Parse("Response line too long".to_owned()))
+                    }
+                    Some(i) => {
+                        self.line.extend_from_slice(&rest[..i + 1]);
+                        used += i + 1;
+                        let line = mem::replace(&mut self.line, Vec::new());
+                        self.end_of_line(line, events)?;
+                    }
+                    None => {
+                        self.line.extend_from_slice(rest);
+                        used = data.len();
+                    }
+                },
+            }
+        }
+        Ok(used)
+    }
+
+    /// Signals that the connection was closed, which ends a body delimited by the close
+    pub fn finish(&mut self, events: &mut Vec<ParseEvent>) -> Result<(), Error> {
+        match self.state {
+            State::Done => Ok(()),
+            State::UntilClose => {
+                self.end(events);
+                Ok(())
+            }
+            _ => Err(Error::Parse(
+                "Connection closed before the end of the response".to_owned(),
+            )),
+        }
+    }
+
+    fn end(&mut self, events: &mut Vec<ParseEvent>) {
+        self.state = State::Done;
+        events.push(ParseEvent::End);
+    }
+
+    fn end_of_line(&mut self, line: Vec<u8>, events: &mut Vec<ParseEvent>) -> Result<(), Error> {
+        let text = trim_line(&line);
+        match self.state {
+            State::StatusLine => {
+                if !text.is_empty() {
+                    self.status_line(text, events)?;
+                    self.header_block = line.clone();
+                    self.block_length = line.len();
+                    self.fields = 0;
+                    self.state = State::Headers;
+                }
+            }
+            State::Headers => {
+                let folded = !text.is_empty() && (text[0] == b' ' || text[0] == b'\t');
+                self.count_line(&line, !text.is_empty() && !folded)?;
+                self.header_block.extend_from_slice(&line);
+                if text.is_empty() {
+                    self.flush_header(events)?;
+                    self.end_of_headers(events)?;
+                } else if folded {
+                    // obsolete line folding continues the previous value (RFC 9112 section 5.2)
+                    let folded = String::from_utf8_lossy(text).trim().to_owned();
+                    match self.pending {
+                        Some((_, ref mut value)) => {
+                            value.push(' ');
+                            value.push_str(&folded);
+                        }
+                        None => return Err(Error::Parse("Folded line without a header".to_owned())),
+                    }
+                } else {
+                    self.flush_header(events)?;
+                    self.pending = Some(parse_field(text)?);
+                }
+            }
+            State::ChunkSize => {
+                let size = parse_chunk_size(text)?;
+                self.state = if size == 0 {
+                    self.block_length = 0;
+                    self.fields = 0;
+                    State::Trailers
+                } else {
+                    State::ChunkData(size)
+                };
+            }
+            State::ChunkDataEnd => {
+                if !text.is_empty() {
+                    return Err(Error::Parse("Missing CRLF after chunk data".to_owned()));
+                }
+                self.state = State::ChunkSize;
+            }
+            State::Trailers => {
+                self.count_line(&line, !text.is_empty())?;
+                if text.is_empty() {
+                    self.end(events);
+                } else {
+                    let (name, value) = parse_field(text)?;
+                    events.push(ParseEvent::Trailer {
+                        name: name,
+                        value: value,
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Counts a line of the header or trailer block against the limits on its size
+    fn count_line(&mut self, line: &[u8], is_field: bool) -> Result<(), Error> {
+        self.block_length += line.len();
+        if is_field {
+            self.fields += 1;
+        }
+        if self.block_length > MAX_BLOCK_LENGTH {
+            return Err(Error::Parse("Response header block too large".to_owned()));
+        }
+        if self.fields > MAX_FIELDS {
+            return Err(Error::Parse("Too many response header fields".to_owned()));
+        }
+        Ok(())
+    }
+
+    fn status_line(&mut self, text: &[u8], events: &mut Vec<ParseEvent>) -> Result<(), Error> {
+        let text = String::from_utf8_lossy(text);
+        let mut parts = text.splitn(3, ' ');
+        let version = parts.next().unwrap_or("");
+        if !version.starts_with("HTTP/") {
+            return Err(Error::Parse("Invalid http response!".to_owned()));
+        }
+        version.parse::<HttpVersion>()?;
+        // a status code is three digits (RFC 9112 section 4)
+        let code = parts
+            .next()
+            .filter(|c| c.len() == 3 && c.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|c| c.parse::<u16>().ok())
+            .ok_or(Error::Parse("Invalid status line".to_owned()))?;
+        self.code = code;
+        self.chunked = None;
+        self.content_length = None;
+        events.push(ParseEvent::Status {
+            version: version.to_owned(),
+            code: code,
+            reason: parts.next().unwrap_or("").trim().to_owned(),
+        });
+        Ok(())
+    }
+
+    /// Reports the last header, now that no folded line can follow it
+    fn flush_header(&mut self, events: &mut Vec<ParseEvent>) -> Result<(), Error> {
+        let (name, value) = match self.pending.take() {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            let last = value.rsplit(',').next().unwrap_or("").trim().to_lowercase();
+            self.chunked = Some(last == "chunked");
+        } else if name.eq_ignore_ascii_case("content-length") {
+            // repeated or list values are only valid when they all agree
+            for v in value.split(',') {
+                let len = v
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| Error::Parse("Invalid Content-Length".to_owned()))?;
+                match self.content_length {
+                    Some(c) if c != len => {
+                        return Err(Error::Parse("Conflicting Content-Length".to_owned()))
+                    }
+                    _ => self.content_length = Some(len),
+                }
+            }
+        }
+        events.push(ParseEvent::Header {
+            name: name,
+            value: value,
+        });
+        Ok(())
+    }
+
+    fn end_of_headers(&mut self, events: &mut Vec<ParseEvent>) -> Result<(), Error> {
+        let code = self.code;
+        // interim responses are followed by the real one
+        if code >= 100 && code < 200 && code != 101 {
+            self.interim += 1;
+            if self.interim > MAX_INTERIM {
+                return Err(Error::Parse("Too many interim responses".to_owned()));
+            }
+            events.push(ParseEvent::HeadersEnd(BodyLength::Empty));
+            self.state = State::StatusLine;
+            return Ok(());
+        }
+
+        let body_length = if self.method == HttpMethods::Head
+            || code == 101
+            || code == 204
+            || code == 304
+            || (self.method == HttpMethods::Connect && code >= 200 && code < 300)
+        {
+            BodyLength::Empty
+        } else {
+            match (self.chunked, self.content_length) {
+                (Some(true), _) => BodyLength::Chunked,
+                (Some(false), _) => BodyLength::UntilClose,
+                (None, Some(l)) => BodyLength::Fixed(l),
+                (None, None) => BodyLength::UntilClose,
+            }
+        };
+
+        events.push(ParseEvent::HeadersEnd(body_length.clone()));
+        self.head = Some(mem::replace(&mut self.header_block, Vec::new()));
+        self.body_length = Some(body_length.clone());
+        match body_length {
+            BodyLength::Empty | BodyLength::Fixed(0) => self.end(events),
+            BodyLength::Fixed(l) => self.state = State::Fixed(l),
+            BodyLength::Chunked => self.state = State::ChunkSize,
+            BodyLength::UntilClose => self.state = State::UntilClose,
+        }
+        Ok(())
+    }
+}
+
+/// Splits a header or trailer line into its name and the value without surrounding whitespace
+pub fn parse_field(text: &[u8]) -> Result<(String, String), Error> {
+    let text = String::from_utf8_lossy(text);
+    let mut parts = text.splitn(2, ':');
+    let name = parts.next().unwrap_or("");
+    let value = parts
+        .next()
+        .ok_or(Error::Parse("Malformed header".to_owned()))?;
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace()) {
+        return Err(Error::Parse(format!("Malformed header name: {}", name)));
+    }
+    Ok((name.to_owned(), value.trim().to_owned()))
+}
+
+/// Strips the line ending from a header or chunk line
+pub fn trim_line(line: &[u8]) -> &[u8] {
+    let mut end = line.len();
+    if end > 0 && line[end - 1] == b'\n' {
+        end -= 1;
+    }
+    if end > 0 && line[end - 1] == b'\r' {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Parses the hex size at the start of a chunk line, ignoring any extensions
+/// like `1a;name=value`
+pub fn parse_chunk_size(line: &[u8]) -> Result<usize, Error> {
+    let size = match line.iter().position(|&b| b == b';') {
+        Some(i) => &line[..i],
+        None => line,
+    };
+    let size = ::std::str::from_utf8(size)
+        .map_err(|e| Error::Parse(format!("Error parsing chunked encoding: {}", e)))?
+        .trim();
+    // from_str_radix would also take a sign
+    if size.is_empty() || !size.bytes().all(|b| (b as char).is_digit(16)) {
+        return Err(Error::Parse(format!("Invalid chunk size: {:?}", size)));
+    }
+    usize::from_str_radix(size, 16)
+        .map_err(|e| Error::Parse(format!("Error parsing chunked encoding: {}", e)))
+}
+
+#[cfg(test)]
+fn parse_all(method: HttpMethods, msg: &[u8], size: usize) -> (usize, Vec<ParseEvent>) {
+    let mut parser = ResponseParser::new(method);
+    let mut events = Vec::new();
+    let mut used = 0;
+    for piece in msg.chunks(size) {
+        used += parser.push(piece, &mut events).unwrap();
+        if parser.is_complete() {
+            break;
+        }
+    }
+    (used, events)
+}
+
+#[test]
+fn parser_content_length_split() {
+    let msg = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhelloHTTP/1.1";
+    let (used, events) = parse_all(HttpMethods::Get, msg, 3);
+    assert_eq!(used, msg.len() - "HTTP/1.1".len());
+    assert_eq!(events.last(), Some(&ParseEvent::End));
+    assert!(events.contains(&ParseEvent::HeadersEnd(BodyLength::Fixed(5))));
+}
+
+#[test]
+fn parser_events_in_any_split() {
+    let msg = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nX-Folded: a\r\n  b\r\n\r\n4;ext=1\r\nwiki\r\n5\r\npedia\r\n0\r\nX-Trailer: yes\r\n\r\n";
+    for size in 1..msg.len() {
+        let (used, events) = parse_all(HttpMethods::Get, msg, size);
+        assert_eq!(used, msg.len());
+        let mut body = Vec::new();
+        let mut others = Vec::new();
+        for event in events {
+            match event {
+                ParseEvent::Body(data) => body.extend_from_slice(&data),
+                event => others.push(event),
+            }
+        }
+        assert_eq!(&body[..], b"wikipedia");
+        assert_eq!(
+            others,
+            vec![
+                ParseEvent::Status {
+                    version: "HTTP/1.1".to_owned(),
+                    code: 200,
+                    reason: "OK".to_owned(),
+                },
+                ParseEvent::Header {
+                    name: "Transfer-Encoding".to_owned(),
+                    value: "chunked".to_owned(),
+                },
+                ParseEvent::Header {
+                    name: "X-Folded".to_owned(),
+                    value: "a b".to_owned(),
+                },
+                ParseEvent::HeadersEnd(BodyLength::Chunked),
+                ParseEvent::Trailer {
+                    name: "X-Trailer".to_owned(),
+                    value: "yes".to_owned(),
+                },
+                ParseEvent::End,
+            ]
+        );
+    }
+}
+
+#[test]
+fn parser_head_ignores_content_length() {
+    let msg = b"HTTP/1.1 200 OK\r\nContent-Length: 500\r\n\r\n";
+    let mut parser = ResponseParser::new(HttpMethods::Head);
+    assert_eq!(parser.push(msg, &mut Vec::new()).unwrap(), msg.len());
+    assert!(parser.is_complete());
+    assert!(parser.head().unwrap().starts_with(b"HTTP/1.1 200 OK\r\n"));
+}
+
+#[test]
+fn parser_no_body_statuses() {
+    let mut events = Vec::new();
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    parser.push(b"HTTP/1.1 304 Not Modified\r\nContent-Length: 10\r\n\r\n", &mut events).unwrap();
+    assert!(parser.is_complete());
+
+    let mut parser = ResponseParser::new(HttpMethods::Connect);
+    parser.push(b"HTTP/1.1 200 Connection established\r\n\r\n", &mut events).unwrap();
+    assert!(parser.is_complete());
+
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    parser
+        .push(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n", &mut events)
+        .unwrap();
+    assert!(!parser.is_complete());
+    assert_eq!(parser.body_length(), Some(BodyLength::Fixed(2)));
+    assert!(parser.head().unwrap().starts_with(b"HTTP/1.1 200 OK\r\n"));
+}
+
+#[test]
+fn parser_until_close() {
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    let mut events = Vec::new();
+    parser.push(b"HTTP/1.0 200 OK\r\n\r\nsome data", &mut events).unwrap();
+    assert!(!parser.is_complete());
+    parser.finish(&mut events).unwrap();
+    assert!(parser.is_complete());
+    assert!(events.contains(&ParseEvent::Body(b"some data".to_vec())));
+    assert_eq!(events.last(), Some(&ParseEvent::End));
+}
+
+#[test]
+fn parser_status_lines() {
+    let (_, events) = parse_all(HttpMethods::Get, b"HTTP/1.0 299 \r\n\r\n", 3);
+    assert_eq!(
+        events[0],
+        ParseEvent::Status {
+            version: "HTTP/1.0".to_owned(),
+            code: 299,
+            reason: String::new(),
+        }
+    );
+    let mut events = Vec::new();
+    for line in &[&b"HTTP/2.0 200 OK\r\n"[..], b"HTTP/1.1 2000 OK\r\n", b"HTTP/1.1 20 OK\r\n"] {
+        let mut parser = ResponseParser::new(HttpMethods::Get);
+        assert!(parser.push(line, &mut events).is_err());
+    }
+}
+
+#[test]
+fn parser_truncated_body() {
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    let mut events = Vec::new();
+    parser.push(b"HTTP/1.1 200 OK\r\nContent-Length: 50\r\n\r\nshort", &mut events).unwrap();
+    assert!(parser.finish(&mut events).is_err());
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    assert!(parser.push(b"SSH-2.0-OpenSSH\r\n", &mut events).is_err());
+}
+
+#[test]
+fn parser_chunk_sizes() {
+    assert_eq!(parse_chunk_size(b"1a;name=val").unwrap(), 26);
+    assert_eq!(parse_chunk_size(b"1A ; name=\"quoted;value\"").unwrap(), 26);
+    assert!(parse_chunk_size(b"+5").is_err());
+    assert!(parse_chunk_size(b";ext").is_err());
+    assert!(parse_chunk_size(b"fffffffffffffffffffff").is_err());
+
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    let mut events = Vec::new();
+    parser.push(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n", &mut events).unwrap();
+    assert!(parser.push(&vec![b'0'; MAX_LINE_LENGTH], &mut events).is_err());
+}
+
+#[test]
+fn parser_header_limits() {
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    let mut events = Vec::new();
+    parser.push(b"HTTP/1.1 200 OK\r\n", &mut events).unwrap();
+    let field = b"X-Many: 1\r\n";
+    for _ in 0..MAX_FIELDS {
+        parser.push(field, &mut events).unwrap();
+    }
+    assert!(parser.push(field, &mut events).is_err());
+
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    parser.push(b"HTTP/1.1 200 OK\r\n", &mut events).unwrap();
+    let mut field = b"X-Long: ".to_vec();
+    field.extend(vec![b'a'; MAX_LINE_LENGTH - 100]);
+    field.extend_from_slice(b"\r\n");
+    let mut pushed = Ok(0);
+    for _ in 0..MAX_BLOCK_LENGTH / field.len() + 1 {
+        pushed = parser.push(&field, &mut events);
+        if pushed.is_err() {
+            break;
+        }
+    }
+    assert!(pushed.is_err());
+
+    // trailers are held to the same limits
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    let head = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n";
+    parser.push(head, &mut events).unwrap();
+    let mut trailers = Vec::new();
+    for _ in 0..MAX_FIELDS + 1 {
+        trailers.extend_from_slice(b"X-Trailer: 1\r\n");
+    }
+    assert!(parser.push(&trailers, &mut events).is_err());
+
+    let mut parser = ResponseParser::new(HttpMethods::Get);
+    let interim = b"HTTP/1.1 102 Processing\r\n\r\n";
+    for _ in 0..MAX_INTERIM {
+        parser.push(interim, &mut events).unwrap();
+    }
+    assert!(parser.push(interim, &mut events).is_err());
+}
+
+#[test]
+fn parser_many_chunks() {
+    let mut msg = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+    for _ in 0..100_000 {
+        msg.extend_from_slice(b"1\r\nx\r\n");
+    }
+    msg.extend_from_slice(b"0\r\n\r\n");
+    let (used, events) = parse_all(HttpMethods::Get, &msg, 4096);
+    assert_eq!(used, msg.len());
+    assert_eq!(events.last(), Some(&ParseEvent::End));
+}
@@ -0,0 +1,274 @@
+extern crate base64;
+extern crate ring;
+extern crate webpki;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::{Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+
+use der::{self, dns_names, ip_addresses, subject_public_key_info, validity};
+use error::Error;
+use signature;
+
+/// Starts the message of the rustls error a pin mismatch is reported with
+pub const PIN_MISMATCH: &str = "No certificate pin matched for ";
+
+/// A public key or certificate that a host is expected to present
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pin {
+    /// The SHA-256 hash of a subjectPublicKeyInfo
+    SpkiSha256(Vec<u8>),
+    /// An exact DER encoded certificate
+    Certificate(Vec<u8>),
+}
+
+impl Pin {
+    /// Returns a pin from a base64 SHA-256 SPKI hash, as printed by
+    /// `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256 -binary | base64`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::{Config, Pin};
+    ///
+    /// let pin = Pin::spki_sha256("rESDikx3klYrRENpoDOgnwtDmzzPzTos3XBarKfBO7s=").unwrap();
+    /// let config = Config::new().pin("localhost", pin);
+    /// ```
+    pub fn spki_sha256(hash: &str) -> Result<Pin, Error> {
+        let hash = base64::decode(hash.trim())
+            .map_err(|e| Error::Certificate(format!("Invalid pin: {}", e)))?;
+        if hash.len() != 32 {
+            return Err(Error::Certificate(format!(
+                "Invalid pin: a SHA-256 hash has 32 bytes, not {}",
+                hash.len()
+            )));
+        }
+        Ok(Pin::SpkiSha256(hash))
+    }
+
+    /// Returns a pin for an exact DER encoded certificate
+    pub fn certificate(der: &[u8]) -> Pin {
+        Pin::Certificate(der.to_vec())
+    }
+
+    /// Returns whether a DER certificate matches the pin
+    pub fn matches(&self, cert: &[u8]) -> bool {
+        match *self {
+            Pin::Certificate(ref der) => der[..] == cert[..],
+            Pin::SpkiSha256(ref hash) => match subject_public_key_info(cert) {
+                Some(spki) => ring::digest::digest(&ring::digest::SHA256, spki).as_ref() == &hash[..],
+                None => false,
+            },
+        }
+    }
+}
+
+/// Verifies pinned hosts by their pins, and every other host with `inner`
+pub struct PinningVerifier {
+    pins: HashMap<String, Vec<Pin>>,
+    fallback: bool,
+    inner: Arc<dyn ServerCertVerifier>,
+}
+
+impl PinningVerifier {
+    /// When `fallback` is set, a pinned host that matches none of its pins is still
+    /// accepted if `inner` verifies it
+    pub fn new(
+        pins: HashMap<String, Vec<Pin>>,
+        fallback: bool,
+        inner: Arc<dyn ServerCertVerifier>,
+    ) -> PinningVerifier {
+        PinningVerifier {
+            pins: pins,
+            fallback: fallback,
+            inner: inner,
+        }
+    }
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented: &[Certificate],
+        dns_name: &str,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let pins = match self.pins.get(&dns_name.to_lowercase()) {
+            Some(pins) => pins,
+            None => return self.inner.verify_server_cert(roots, presented, dns_name, ocsp_response),
+        };
+        let matched = presented
+            .iter()
+            .position(|cert| pins.iter().any(|pin| pin.matches(&cert.0)))
+            .filter(|&i| issued_chain(&presented[..i + 1]));
+        match matched {
+            // the server holds the pinned key of its own certificate, which stands in for
+            // a trust anchor but must still be current and issued for the host
+            Some(0) => {
+                check_leaf(&presented[0].0, dns_name)?;
+                Ok(ServerCertVerified::assertion())
+            }
+            // a pinned issuer signed its way down to the leaf, which must still be valid
+            Some(_) => self.inner.verify_server_cert(roots, presented, dns_name, ocsp_response),
+            None if self.fallback => {
+                warn!("No certificate pin matched for {}, verifying the chain instead", dns_name);
+                self.inner.verify_server_cert(roots, presented, dns_name, ocsp_response)
+            }
+            None => Err(TLSError::General(format!("{}{}", PIN_MISMATCH, dns_name))),
+        }
+    }
+}
+
+/// Returns whether every certificate of `chain` after the first signed the one before it
+fn issued_chain(chain: &[Certificate]) -> bool {
+    chain.windows(2).all(|pair| {
+        match (der::signed(&pair[0].0), der::spki_contents(&pair[1].0)) {
+            (Some(signed), Some(spki)) => signature::signed_by(&signed, spki),
+            _ => false,
+        }
+    })
+}
+
+/// Checks that a certificate is within its validity period and names `host`
+fn check_leaf(cert: &[u8], host: &str) -> Result<(), TLSError> {
+    let (not_before, not_after) =
+        validity(cert).ok_or(TLSError::WebPKIError(webpki::Error::BadDER))?;
+    let now = SystemTime::now();
+    if now < not_before {
+        return Err(TLSError::WebPKIError(webpki::Error::CertNotValidYet));
+    }
+    if now > not_after {
+        return Err(TLSError::WebPKIError(webpki::Error::CertExpired));
+    }
+    let named = match host.parse::<IpAddr>() {
+        Ok(ip) => ip_addresses(cert).contains(&ip),
+        Err(_) => dns_names(cert).iter().any(|name| name_matches(name, host)),
+    };
+    if named {
+        Ok(())
+    } else {
+        Err(TLSError::WebPKIError(webpki::Error::CertNotValidForName))
+    }
+}
+
+/// Matches a dNSName, which may start with a `*.` wildcard for a single label, against a host
+fn name_matches(name: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    if name.starts_with("*.") {
+        match host.find('.') {
+            Some(i) if i > 0 => host[i + 1..].eq_ignore_ascii_case(&name[2..]),
+            _ => false,
+        }
+    } else {
+        name.eq_ignore_ascii_case(host)
+    }
+}
+
+/// A verifier standing in for the chain validation that pinning wraps
+#[cfg(test)]
+pub enum StubVerifier {
+    Accept,
+    Reject,
+}
+
+#[cfg(test)]
+impl ServerCertVerifier for StubVerifier {
+    fn verify_server_cert(
+        &self,
+        _: &RootCertStore,
+        _: &[Certificate],
+        _: &str,
+        _: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        match *self {
+            StubVerifier::Accept => Ok(ServerCertVerified::assertion()),
+            StubVerifier::Reject => Err(TLSError::General("rejected".to_owned())),
+        }
+    }
+}
+
+#[test]
+fn pin_spki_sha256() {
+    let cert = ::der::pem_to_der(include_bytes!("../test_server/https.pem"));
+    let pin = Pin::spki_sha256("rESDikx3klYrRENpoDOgnwtDmzzPzTos3XBarKfBO7s=").unwrap();
+    assert!(pin.matches(&cert));
+    let other = Pin::spki_sha256("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=").unwrap();
+    assert!(!other.matches(&cert));
+    assert!(Pin::spki_sha256("c2hvcnQ=").is_err());
+    assert!(Pin::certificate(&cert).matches(&cert));
+}
+
+#[test]
+fn pin_mismatch_is_distinct() {
+    let cert = Certificate(::der::pem_to_der(include_bytes!("../test_server/ip.pem")));
+    let mut pins = HashMap::new();
+    pins.insert("127.0.0.1".to_owned(), vec![Pin::certificate(b"another certificate")]);
+    let verifier = PinningVerifier::new(pins.clone(), false, Arc::new(StubVerifier::Reject));
+    let roots = RootCertStore::empty();
+    match verifier.verify_server_cert(&roots, &[cert.clone()], "127.0.0.1", &[]) {
+        Err(e) => match Error::from(e) {
+            Error::PinMismatch(host) => assert_eq!(host, "127.0.0.1"),
+            e => panic!("unexpected error: {}", e),
+        },
+        Ok(_) => panic!("expected a pin mismatch"),
+    }
+
+    pins.get_mut("127.0.0.1").unwrap().push(Pin::certificate(&cert.0));
+    let verifier = PinningVerifier::new(pins, false, Arc::new(StubVerifier::Reject));
+    assert!(verifier.verify_server_cert(&roots, &[cert], "127.0.0.1", &[]).is_ok());
+}
+
+#[test]
+fn pinned_issuer_must_sign_the_chain() {
+    let issuer = Certificate(::der::pem_to_der(include_bytes!("../test_server/crl_ca.pem")));
+    let mut pins = HashMap::new();
+    pins.insert("127.0.0.1".to_owned(), vec![Pin::certificate(&issuer.0)]);
+    let verifier = PinningVerifier::new(pins, false, Arc::new(StubVerifier::Accept));
+    let roots = RootCertStore::empty();
+
+    // issued by ca.pem, the pinned certificate only rides along
+    let unrelated = Certificate(::der::pem_to_der(include_bytes!("../test_server/ip.pem")));
+    match verifier.verify_server_cert(&roots, &[unrelated, issuer.clone()], "127.0.0.1", &[]) {
+        Err(e) => match Error::from(e) {
+            Error::PinMismatch(host) => assert_eq!(host, "127.0.0.1"),
+            e => panic!("unexpected error: {}", e),
+        },
+        Ok(_) => panic!("expected a pin mismatch"),
+    }
+
+    let leaf = Certificate(::der::pem_to_der(include_bytes!("../test_server/revoked.pem")));
+    assert!(verifier.verify_server_cert(&roots, &[leaf, issuer], "127.0.0.1", &[]).is_ok());
+}
+
+#[test]
+fn pinned_certificate_is_still_validated() {
+    let roots = RootCertStore::empty();
+    // valid for 127.0.0.1 and ::1 only
+    let cert = Certificate(::der::pem_to_der(include_bytes!("../test_server/ip.pem")));
+    let mut pins = HashMap::new();
+    pins.insert("localhost".to_owned(), vec![Pin::certificate(&cert.0)]);
+    let verifier = PinningVerifier::new(pins, false, Arc::new(StubVerifier::Reject));
+    match verifier.verify_server_cert(&roots, &[cert], "localhost", &[]) {
+        Err(TLSError::WebPKIError(webpki::Error::CertNotValidForName)) => {}
+        _ => panic!("expected the name to be checked"),
+    }
+
+    // expired in 2020
+    let cert = Certificate(::der::pem_to_der(include_bytes!("../test_server/https.pem")));
+    let mut pins = HashMap::new();
+    pins.insert("localhost".to_owned(), vec![Pin::certificate(&cert.0)]);
+    let verifier = PinningVerifier::new(pins, false, Arc::new(StubVerifier::Reject));
+    match verifier.verify_server_cert(&roots, &[cert], "localhost", &[]) {
+        Err(TLSError::WebPKIError(webpki::Error::CertExpired)) => {}
+        _ => panic!("expected the validity period to be checked"),
+    }
+
+    assert!(name_matches("*.example.com", "api.example.com"));
+    assert!(name_matches("API.example.com", "api.example.com."));
+    assert!(!name_matches("*.example.com", "example.com"));
+    assert!(!name_matches("*.example.com", "a.b.example.com"));
+}
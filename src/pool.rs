@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use transports::Transport;
+
+/// Identifies the connections that can serve a url
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PoolKey {
+    scheme: String,
+    host: String,
+    port: u16,
+    server_name: Option<String>,
+}
+
+impl PoolKey {
+    pub fn from_url(url: &Url) -> Option<PoolKey> {
+        match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => Some(PoolKey {
+                scheme: url.scheme().to_owned(),
+                host: host.to_owned(),
+                port: port,
+                server_name: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Keeps connections verified for a tls server name override apart from the others
+    pub fn with_server_name(mut self, server_name: Option<String>) -> PoolKey {
+        self.server_name = server_name;
+        self
+    }
+}
+
+struct IdleConnection {
+    transport: Box<Transport>,
+    since: Instant,
+}
+
+struct PoolState {
+    idle: HashMap<PoolKey, Vec<IdleConnection>>,
+    max_idle_per_host: usize,
+    idle_timeout: Duration,
+}
+
+/// Idle keep-alive connections, shared between a client and the responses it hands out
+#[derive(Clone)]
+pub struct Pool {
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl Pool {
+    pub fn new(max_idle_per_host: usize, idle_timeout: Duration) -> Pool {
+        Pool {
+            state: Arc::new(Mutex::new(PoolState {
+                idle: HashMap::new(),
+                max_idle_per_host: max_idle_per_host,
+                idle_timeout: idle_timeout,
+            })),
+        }
+    }
+
+    /// Takes the most recently used live connection for `key`, dropping expired or stale ones
+    pub fn checkout(&self, key: &PoolKey) -> Option<Box<Transport>> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return None,
+        };
+        let idle_timeout = state.idle_timeout;
+        let connections = state.idle.get_mut(key)?;
+        while let Some(mut idle) = connections.pop() {
+            if idle.since.elapsed() >= idle_timeout {
+                trace!("Dropping expired connection to {:?}", key);
+                continue;
+            }
+            if idle.transport.is_stale() {
+                trace!("Dropping stale connection to {:?}", key);
+                continue;
+            }
+            return Some(idle.transport);
+        }
+        None
+    }
+
+    /// Returns a connection that finished its last exchange cleanly
+    pub fn checkin(&self, key: PoolKey, transport: Box<Transport>) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        if state.max_idle_per_host == 0 {
+            return;
+        }
+        let max_idle = state.max_idle_per_host;
+        let idle_timeout = state.idle_timeout;
+        let connections = state.idle.entry(key).or_insert_with(Vec::new);
+        connections.retain(|c| c.since.elapsed() < idle_timeout);
+        if connections.len() >= max_idle {
+            // the oldest connection makes room
+            connections.remove(0);
+        }
+        connections.push(IdleConnection {
+            transport: transport,
+            since: Instant::now(),
+        });
+    }
+
+    /// Returns the number of idle connections held for `key`
+    pub fn idle_count(&self, key: &PoolKey) -> usize {
+        match self.state.lock() {
+            Ok(state) => state.idle.get(key).map(|c| c.len()).unwrap_or(0),
+            Err(_) => 0,
+        }
+    }
+}
+
+/// Where a connection goes back to once its response has been read
+pub struct PoolSlot {
+    pub pool: Pool,
+    pub key: PoolKey,
+}
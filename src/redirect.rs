@@ -0,0 +1,182 @@
+use std::fmt;
+use std::sync::Arc;
+
+use url::Url;
+
+use constants::{HttpMethods, ResponseCode};
+use error::Error;
+use request::Request;
+
+/// Decides whether redirect responses are followed
+#[derive(Clone)]
+pub enum RedirectPolicy {
+    /// Redirect responses are returned as they are
+    None,
+    /// Follows up to this many redirects, then fails with `Error::TooManyRedirects`.
+    /// `Limited(0)` fails on the first redirect, use `None` to get it back as a response.
+    Limited(usize),
+    /// Called with the next url and the urls visited so far, follows the redirect if it returns true
+    Custom(Arc<dyn Fn(&Url, &[Url]) -> bool + Send + Sync>),
+}
+
+impl RedirectPolicy {
+    /// Returns a policy that asks `f` about every redirect
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hasty::{Config, RedirectPolicy};
+    ///
+    /// // only follow redirects that stay on the same host
+    /// let policy = RedirectPolicy::custom(|next, previous| {
+    ///     previous.last().map(|p| p.host_str() == next.host_str()).unwrap_or(true)
+    /// });
+    /// let config = Config::new().redirect_policy(policy);
+    /// ```
+    pub fn custom<F>(f: F) -> RedirectPolicy
+    where
+        F: Fn(&Url, &[Url]) -> bool + Send + Sync + 'static,
+    {
+        RedirectPolicy::Custom(Arc::new(f))
+    }
+
+    /// Returns whether to follow a redirect to `next`, given the urls visited so far
+    pub fn follows(&self, next: &Url, previous: &[Url]) -> Result<bool, Error> {
+        match *self {
+            RedirectPolicy::None => Ok(false),
+            RedirectPolicy::Limited(max) if previous.len() > max => {
+                Err(Error::TooManyRedirects(max))
+            }
+            RedirectPolicy::Limited(_) => Ok(true),
+            RedirectPolicy::Custom(ref f) => Ok(f(next, previous)),
+        }
+    }
+}
+
+impl fmt::Debug for RedirectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RedirectPolicy::None => write!(f, "RedirectPolicy::None"),
+            RedirectPolicy::Limited(max) => write!(f, "RedirectPolicy::Limited({})", max),
+            RedirectPolicy::Custom(_) => write!(f, "RedirectPolicy::Custom"),
+        }
+    }
+}
+
+/// Builds the request for the next hop of a redirect (RFC 9110 section 15.4).
+///
+/// Returns `None` when the response is not a redirect that can be followed.
+pub fn next_request(
+    request: &Request,
+    current: &Url,
+    response_code: &ResponseCode,
+    location: Option<String>,
+) -> Option<Request> {
+    let keep_method = match *response_code {
+        ResponseCode::MovedPermanently | ResponseCode::Found => {
+            request.method() != HttpMethods::Post
+        }
+        ResponseCode::SeeOther => request.method() == HttpMethods::Head,
+        ResponseCode::TemporaryRedirect | ResponseCode::PermanentRedirect => true,
+        _ => return None,
+    };
+
+    let mut next_url = match current.join(&location?) {
+        Ok(url) => url,
+        Err(e) => {
+            warn!("Ignoring redirect with an invalid location: {}", e);
+            return None;
+        }
+    };
+    if next_url.fragment().is_none() {
+        next_url.set_fragment(current.fragment());
+    }
+
+    let mut next = request.clone();
+    if keep_method {
+        if let Some(body) = request.body() {
+            if !body.is_replayable() {
+                debug!("Not following redirect, the request body was streamed");
+                return None;
+            }
+        }
+    } else {
+        next.set_method(HttpMethods::Get);
+        next.set_body(None);
+    }
+
+    if current.origin() != next_url.origin() {
+        next.remove_header("authorization");
+        next.remove_header("cookie");
+        // the override named the old host's certificate
+        next.set_tls_server_name(None);
+    }
+    next.set_url(next_url);
+    Some(next)
+}
+
+#[test]
+fn redirect_see_other_becomes_get() {
+    let current = Url::parse("http://localhost:3000/form").unwrap();
+    let request = Request::from_url(current.clone())
+        .with_method(HttpMethods::Post)
+        .with_body(Some(b"data".to_vec()));
+    let next = next_request(
+        &request,
+        &current,
+        &ResponseCode::SeeOther,
+        Some("/done".to_owned()),
+    ).unwrap();
+    assert_eq!(next.method(), HttpMethods::Get);
+    assert!(next.body().is_none());
+    assert_eq!(next.url().unwrap().as_str(), "http://localhost:3000/done");
+}
+
+#[test]
+fn redirect_temporary_keeps_method_and_body() {
+    let current = Url::parse("http://localhost:3000/upload").unwrap();
+    let request = Request::from_url(current.clone())
+        .with_method(HttpMethods::Put)
+        .with_body(Some(b"data".to_vec()));
+    let next = next_request(
+        &request,
+        &current,
+        &ResponseCode::TemporaryRedirect,
+        Some("http://localhost:3000/upload2".to_owned()),
+    ).unwrap();
+    assert_eq!(next.method(), HttpMethods::Put);
+    assert!(next.body().is_some());
+}
+
+#[test]
+fn redirect_cross_origin_drops_credentials() {
+    let current = Url::parse("https://localhost:3001/private").unwrap();
+    let request = Request::from_url(current.clone())
+        .with_raw_header("Authorization".to_owned(), "Bearer token".to_owned())
+        .with_raw_header("Cookie".to_owned(), "session=1".to_owned())
+        .with_raw_header("X-Other".to_owned(), "kept".to_owned())
+        .with_tls_server_name("internal.example.com");
+    let next = next_request(
+        &request,
+        &current,
+        &ResponseCode::Found,
+        Some("https://example.com/".to_owned()),
+    ).unwrap();
+    assert!(next.get_header("authorization").is_none());
+    assert!(next.get_header("cookie").is_none());
+    assert_eq!(next.get_header("x-other"), Some("kept".to_owned()));
+    assert!(next.tls_server_name().is_none());
+}
+
+#[test]
+fn redirect_limited_policy() {
+    let next = Url::parse("http://localhost:3000/").unwrap();
+    let policy = RedirectPolicy::Limited(1);
+    assert!(policy.follows(&next, &[next.clone()]).unwrap());
+    assert!(policy.follows(&next, &[next.clone(), next.clone()]).is_err());
+    match RedirectPolicy::Limited(0).follows(&next, &[next.clone()]) {
+        Err(Error::TooManyRedirects(0)) => {}
+        _ => panic!("expected the first redirect to fail"),
+    }
+    assert!(!RedirectPolicy::None.follows(&next, &[next.clone()]).unwrap());
+}
@@ -1,20 +1,27 @@
 extern crate mime;
 
-use std::collections::HashMap;
+use std::io::{Read, Write};
 
+use url::form_urlencoded;
 use url::Url;
 
+use body::RequestBody;
 use constants::HttpMethods;
+use error::Error;
+use headers::HeaderMap;
 
 #[derive(Clone)]
 pub struct Request {
     host: String,
     path: String,
     method: HttpMethods,
-    headers: HashMap<String,String>,
-    body: Option<Vec<u8>>,
+    headers: HeaderMap,
+    body: Option<RequestBody>,
     body_type: mime::Mime,
     url: Option<Url>,
+    query: Vec<(String, String)>,
+    absolute_form: bool,
+    tls_server_name: Option<String>,
 }
 
 impl Request {
@@ -24,10 +31,13 @@ impl Request {
             host: "".to_string(),
             path: "/".to_string(),
             method: HttpMethods::Get,
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body: None,
             body_type: mime::TEXT_PLAIN,
-            url: None
+            url: None,
+            query: Vec::new(),
+            absolute_form: false,
+            tls_server_name: None,
         }
     }
 
@@ -48,15 +58,7 @@ impl Request {
     /// }
     /// ```
     pub fn from_url(url: Url) -> Request {
-        Request {
-            host: url.host_str().unwrap().to_string(),
-            path: url.path().to_owned(),
-            method: HttpMethods::Get,
-            headers: HashMap::new(),
-            body: None,
-            body_type: mime::TEXT_PLAIN,
-            url: Some(url),
-        }
+        Request::new().with_url(url)
     }
 
     /// Set the host and path based on provided url
@@ -76,8 +78,11 @@ impl Request {
     ///     assert_eq!(req.url().unwrap(), url);
     /// }
     /// ```
-    pub fn set_url(&mut self, url: Url) {
-        self.host = url.host_str().unwrap().to_string();
+    pub fn set_url(&mut self, mut url: Url) {
+        if !self.query.is_empty() {
+            url.query_pairs_mut().extend_pairs(self.query.drain(..));
+        }
+        self.host = url.host_str().unwrap_or("").to_string();
         self.path = url.path().to_owned();
         self.url = Some(url);
     }
@@ -87,11 +92,103 @@ impl Request {
         self.url.clone()
     }
 
+    /// Appends a percent-encoded name/value pair to the query string
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate hasty;
+    /// extern crate url;
+    ///
+    /// use hasty::Request;
+    /// use url::Url;
+    ///
+    /// fn main() {
+    ///     let url = url::Url::parse("http://www.rust-lang.org/search").unwrap();
+    ///     let mut req = Request::from_url(url);
+    ///     req.add_query_pair("q", "hasty & fast");
+    ///     assert_eq!(req.target(), "/search?q=hasty+%26+fast");
+    /// }
+    /// ```
+    pub fn add_query_pair(&mut self, name: &str, value: &str) {
+        match self.url {
+            Some(ref mut url) => {
+                url.query_pairs_mut().append_pair(name, value);
+            }
+            None => self.query.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// Sends the whole url as the request target, as forward proxies expect
+    /// (RFC 9112 section 3.2.2)
+    pub fn set_absolute_form(&mut self, absolute_form: bool) {
+        self.absolute_form = absolute_form;
+    }
+
+    /// Sets the name sent as SNI and checked against the server certificate,
+    /// instead of the host of the url. The connection still goes to the url's host.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate hasty;
+    /// extern crate url;
+    ///
+    /// use hasty::Request;
+    ///
+    /// fn main() {
+    ///     let url = url::Url::parse("https://10.0.0.5/status").unwrap();
+    ///     let mut req = Request::from_url(url);
+    ///     req.set_tls_server_name(Some("api.example.com".to_owned()));
+    ///     assert_eq!(req.tls_server_name(), Some("api.example.com".to_owned()));
+    /// }
+    /// ```
+    pub fn set_tls_server_name(&mut self, name: Option<String>) {
+        self.tls_server_name = name;
+    }
+
+    /// Returns the tls server name override
+    pub fn tls_server_name(&self) -> Option<String> {
+        self.tls_server_name.clone()
+    }
+
+    /// Returns the absolute-form request target (the url without its fragment)
+    pub fn absolute_target(&self) -> Option<String> {
+        self.url.as_ref().map(|url| {
+            let mut url = url.clone();
+            url.set_fragment(None);
+            url.into_string()
+        })
+    }
+
+    /// Returns the origin-form request target (path and query, never the fragment)
+    pub fn target(&self) -> String {
+        match self.url {
+            Some(ref url) => match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_owned(),
+            },
+            None if self.query.is_empty() => self.path.clone(),
+            None => format!(
+                "{}?{}",
+                self.path,
+                form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(self.query.iter())
+                    .finish()
+            ),
+        }
+    }
+
     /// Set the HTTP method
     pub fn set_method(&mut self, method: HttpMethods) {
         self.method = method;
     }
 
+    /// Get the HTTP method
+    pub fn method(&self) -> HttpMethods {
+        self.method.clone()
+    }
+
     /// Set the content type for the reqeust body
     pub fn set_content_type(&mut self, content_type: mime::Mime) {
         self.body_type = content_type;
@@ -99,7 +196,40 @@ impl Request {
 
     /// Set the payload of the request
     pub fn set_body(&mut self, body: Option<Vec<u8>>) {
-        self.body = body;
+        self.body = body.map(RequestBody::from_bytes);
+    }
+
+    /// Set the payload of the request to be streamed from a reader.
+    ///
+    /// With a known `length` the body is sent with a Content-Length,
+    /// otherwise it is sent with chunked transfer encoding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate hasty;
+    /// extern crate url;
+    ///
+    /// use std::io::Cursor;
+    /// use hasty::{HttpMethods, Request};
+    /// use url::Url;
+    ///
+    /// fn main() {
+    ///     let url = url::Url::parse("http://www.rust-lang.org/upload").unwrap();
+    ///     let mut req = Request::from_url(url).with_method(HttpMethods::Put);
+    ///     req.set_body_reader(Cursor::new(b"evidence".to_vec()), None);
+    ///     let payload = String::from_utf8(req.to_payload()).unwrap();
+    ///     assert!(payload.contains("transfer-encoding: chunked\r\n"));
+    ///     assert!(payload.ends_with("\r\n\r\n8\r\nevidence\r\n0\r\n\r\n"));
+    /// }
+    /// ```
+    pub fn set_body_reader<R: Read + Send + 'static>(&mut self, reader: R, length: Option<u64>) {
+        self.body = Some(RequestBody::from_reader(reader, length));
+    }
+
+    /// Returns the payload of the request
+    pub fn body(&self) -> Option<RequestBody> {
+        self.body.clone()
     }
 
     /// Adds a user defined header to the request
@@ -110,18 +240,71 @@ impl Request {
     /// let req = Request::new().add_raw_header("Authentication".to_owned(), "MyApiKey".to_owned());
     /// ```
     pub fn add_raw_header(&mut self, name: String, value: String) {
-        self.headers.insert(name, value);
+        self.headers.append(&name, &value);
+    }
+
+    /// Returns the first value of a user defined header, matching the name case-insensitively
+    pub fn get_header(&self, name: &str) -> Option<String> {
+        self.headers.get(name).map(|v| v.to_owned())
+    }
+
+    /// Removes a user defined header, matching the name case-insensitively
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.remove(name);
+    }
+
+    /// Returns the user defined headers
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the user defined headers for changing
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
     }
 
     pub fn with_raw_header(mut self, name: String, value: String) -> Self {
-        self.headers.insert(name, value);
+        self.headers.append(&name, &value);
         self
     }
 
     pub fn with_url(mut self, url: Url) -> Self {
-        self.host = url.host().unwrap().to_string();
-        self.path = url.path().to_owned();
-        self.url = Some(url);
+        self.set_url(url);
+        self
+    }
+
+    /// Appends a percent-encoded name/value pair to the query string
+    pub fn with_query_pair(mut self, name: &str, value: &str) -> Self {
+        self.add_query_pair(name, value);
+        self
+    }
+
+    /// Appends several percent-encoded name/value pairs to the query string
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate hasty;
+    /// extern crate url;
+    ///
+    /// use hasty::Request;
+    /// use url::Url;
+    ///
+    /// fn main() {
+    ///     let url = url::Url::parse("http://www.rust-lang.org/items?sort=asc").unwrap();
+    ///     let req = Request::from_url(url).with_query_params(vec![("page", "2"), ("limit", "50")]);
+    ///     assert_eq!(req.target(), "/items?sort=asc&page=2&limit=50");
+    /// }
+    /// ```
+    pub fn with_query_params<I, K, V>(mut self, params: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (name, value) in params {
+            self.add_query_pair(name.as_ref(), value.as_ref());
+        }
         self
     }
 
@@ -139,7 +322,19 @@ impl Request {
 
     /// Set the payload of the request
     pub fn with_body(mut self, body: Option<Vec<u8>>) -> Self {
-        self.body = body;
+        self.set_body(body);
+        self
+    }
+
+    /// Set the payload of the request to be streamed from a reader
+    pub fn with_body_reader<R: Read + Send + 'static>(mut self, reader: R, length: Option<u64>) -> Self {
+        self.set_body_reader(reader, length);
+        self
+    }
+
+    /// Set the name used for SNI and certificate verification
+    pub fn with_tls_server_name(mut self, name: &str) -> Self {
+        self.set_tls_server_name(Some(name.to_owned()));
         self
     }
 
@@ -163,36 +358,125 @@ impl Request {
     /// ```
     pub fn to_payload(self) -> Vec<u8> {
         let mut payload = Vec::new();
-        payload.extend(
+        // writing to memory only fails if a reader body does, which cuts the body short
+        let _ = self.write_to(&mut payload);
+        payload
+    }
+
+    /// Writes the request to `out`, streaming the body if it comes from a reader
+    pub fn write_to<W: Write>(self, out: &mut W) -> Result<(), Error> {
+        let target = match self.method {
+            // CONNECT uses the authority-form target (RFC 9112 section 3.2.3)
+            HttpMethods::Connect => match self.url.as_ref().and_then(|u| u.port_or_known_default()) {
+                Some(port) => format!("{}:{}", self.host, port),
+                None => self.host.clone(),
+            },
+            _ if self.absolute_form => self.absolute_target().unwrap_or_else(|| self.target()),
+            _ => self.target(),
+        };
+        let body = match self.method {
+            // a client must not send content in a TRACE request (RFC 9110 section 9.3.8)
+            HttpMethods::Trace => None,
+            _ => self.body,
+        };
+        let mut head = Vec::new();
+        head.extend(
             format!(
                 "{} {} HTTP/1.1\r\n",
-                self.method.to_string(), self.path
+                self.method.to_string(), target
             ).as_bytes()
         );
-        payload.extend(
+        // the Host of a CONNECT request names the same authority as its target
+        let host = match self.method {
+            HttpMethods::Connect => &target,
+            _ => &self.host,
+        };
+        head.extend(
             format!(
-                "Host: {}\r\n", self.host
+                "Host: {}\r\n", host
             ).as_bytes()
         );
-        for header in self.headers {
-            payload.extend(
+        for (name, value) in self.headers.iter() {
+            head.extend(
                 format!(
-                    "{}: {}\r\n", header.0, header.1
+                    "{}: {}\r\n", name, value
                 ).as_bytes()
             );
         }
-        if let Some(content) = self.body {
-            payload.extend(
+        if let Some(ref content) = body {
+            match content.content_length() {
+                Some(length) => head.extend(format!("content-length: {}\r\n", length).as_bytes()),
+                None => head.extend(b"transfer-encoding: chunked\r\n"),
+            }
+            head.extend(
                 format!(
-                    "content-length: {}\r\ncontent-type: {};charset=UTF-8\r\n\r\n",
-                    content.len(), self.body_type
+                    "content-type: {};charset=UTF-8\r\n", self.body_type
                 ).as_bytes()
             );
-            payload.extend(content);
         }
-        payload.extend(
-            "\r\n".to_owned().into_bytes()
-        );
-        payload
+        head.extend(b"\r\n");
+        out.write_all(&head)?;
+        if let Some(content) = body {
+            content.write_to(out)?;
+        }
+        Ok(())
     }
 }
+
+#[test]
+fn request_keeps_query_string() {
+    let url = Url::parse("http://localhost:3000/items?page=2&limit=50#top").unwrap();
+    let req = Request::from_url(url);
+    let payload = String::from_utf8(req.to_payload()).unwrap();
+    assert!(payload.starts_with("GET /items?page=2&limit=50 HTTP/1.1\r\n"));
+}
+
+#[test]
+fn request_absolute_form() {
+    let url = Url::parse("http://localhost:3000/items?page=2#top").unwrap();
+    let mut req = Request::from_url(url);
+    req.set_absolute_form(true);
+    let payload = String::from_utf8(req.to_payload()).unwrap();
+    assert!(payload.starts_with("GET http://localhost:3000/items?page=2 HTTP/1.1\r\n"));
+}
+
+#[test]
+fn request_connect_authority() {
+    let url = Url::parse("https://proxied.test:8443/").unwrap();
+    let req = Request::from_url(url).with_method(HttpMethods::Connect);
+    let payload = String::from_utf8(req.to_payload()).unwrap();
+    assert!(payload.starts_with("CONNECT proxied.test:8443 HTTP/1.1\r\n"));
+    assert!(payload.contains("\r\nHost: proxied.test:8443\r\n"));
+}
+
+#[test]
+fn request_query_pairs_before_url() {
+    let mut req = Request::new().with_query_pair("name", "a b");
+    assert_eq!(req.target(), "/?name=a+b");
+    req.set_url(Url::parse("http://localhost:3000/search").unwrap());
+    assert_eq!(req.target(), "/search?name=a+b");
+    assert_eq!(req.url().unwrap().query(), Some("name=a+b"));
+}
+
+#[test]
+fn request_body_ends_payload() {
+    let url = Url::parse("http://localhost:3000/basic_post").unwrap();
+    let req = Request::from_url(url)
+        .with_method(HttpMethods::Post)
+        .with_body(Some(b"abcd".to_vec()));
+    let payload = String::from_utf8(req.to_payload()).unwrap();
+    assert!(payload.contains("content-length: 4\r\n"));
+    assert!(payload.ends_with("\r\n\r\nabcd"));
+}
+
+#[test]
+fn request_repeated_headers_in_order() {
+    let url = Url::parse("http://localhost:3000/").unwrap();
+    let req = Request::from_url(url)
+        .with_raw_header("Cookie".to_owned(), "a=1".to_owned())
+        .with_raw_header("X-Trace".to_owned(), "on".to_owned())
+        .with_raw_header("cookie".to_owned(), "b=2".to_owned());
+    assert_eq!(req.get_header("COOKIE"), Some("a=1".to_owned()));
+    let payload = String::from_utf8(req.to_payload()).unwrap();
+    assert!(payload.contains("Cookie: a=1\r\nX-Trace: on\r\ncookie: b=2\r\n"));
+}
@@ -1,15 +1,24 @@
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::io::prelude::*;
+use url::Url;
 
-use constants::{ResponseCode, TransferEncoding};
+use constants::{HttpMethods, HttpVersion, ResponseCode, TransferEncoding};
+use error::Error;
+use headers::HeaderMap;
+use parser::{BodyLength, ParseEvent, ResponseParser};
 use request::Request;
-use transports::{find_sub_vector, Transport};
+use streaming::StreamingResponse;
+use tls_info::TlsInfo;
+use transports::Transport;
 
 pub struct Response {
     response_code: ResponseCode,
-    headers: HashMap<String, String>,
+    reason: String,
+    version: HttpVersion,
+    headers: HeaderMap,
     body: Vec<u8>,
+    trailers: HeaderMap,
+    url: Option<Url>,
+    redirects: Vec<Url>,
+    tls_info: Option<TlsInfo>,
 }
 
 impl Response {
@@ -17,8 +26,14 @@ impl Response {
     pub fn new() -> Response {
         Response {
             response_code: ResponseCode::Ok,
-            headers: HashMap::new(),
+            reason: String::new(),
+            version: HttpVersion::Http11,
+            headers: HeaderMap::new(),
             body: Vec::new(),
+            trailers: HeaderMap::new(),
+            url: None,
+            redirects: Vec::new(),
+            tls_info: None,
         }
     }
 
@@ -32,22 +47,79 @@ impl Response {
         self.response_code.clone()
     }
 
+    /// Returns the reason phrase of the status line, like `Not Found`
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Returns the protocol version of the status line
+    pub fn version(&self) -> HttpVersion {
+        self.version
+    }
+
     /// Adds a header to the response
     pub fn add_header(&mut self, name: String, value: String) {
-        self.headers.insert(name, value);
+        self.headers.append(&name, &value);
     }
 
-    /// Returns a copy of the header HashMap
-    pub fn headers(&self) -> HashMap<String, String> {
-        self.headers.clone()
+    /// Returns the headers, in the order they were received
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
     }
 
     /// Returns an option of a specific header
     pub fn get_header(&mut self, name: String) -> Option<String> {
-        match self.headers.entry(name) {
-            Entry::Occupied(e) => Some(e.get().to_owned()),
-            _ => None,
-        }
+        self.header(&name)
+    }
+
+    /// Returns the first value of a header, matching the name case-insensitively
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.headers.get(name).map(|v| v.to_owned())
+    }
+
+    /// Sets the trailer fields that followed a chunked body
+    pub fn set_trailers(&mut self, trailers: HeaderMap) {
+        self.trailers = trailers;
+    }
+
+    /// Returns the trailer fields that followed a chunked body
+    pub fn trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+
+    /// Returns the first value of a trailer field, matching the name case-insensitively
+    pub fn trailer(&self, name: &str) -> Option<String> {
+        self.trailers.get(name).map(|v| v.to_owned())
+    }
+
+    /// Sets the url the response was received from
+    pub fn set_url(&mut self, url: Url) {
+        self.url = Some(url);
+    }
+
+    /// Returns the effective url, after any redirects were followed
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    /// Sets the urls that redirected to this response
+    pub fn set_redirects(&mut self, redirects: Vec<Url>) {
+        self.redirects = redirects;
+    }
+
+    /// Returns the urls that redirected to this response, in the order they were visited
+    pub fn redirects(&self) -> Vec<Url> {
+        self.redirects.clone()
+    }
+
+    /// Sets the details of the TLS session the response was received over
+    pub fn set_tls_info(&mut self, tls_info: Option<TlsInfo>) {
+        self.tls_info = tls_info;
+    }
+
+    /// Returns the details of the TLS session, None for plain http
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.tls_info.as_ref()
     }
 
     /// Sets the body payload
@@ -60,123 +132,80 @@ impl Response {
         self.body.clone()
     }
 
-    pub fn parse_headers(&mut self, data: &[u8]) -> Result<TransferEncoding, String> {
-        let mut transfer_encoding = TransferEncoding::Normal;
-        let header_text = String::from_utf8(data.to_vec()).map_err(|e| e.to_string())?;
-        let mut header_lines = header_text.lines();
-        if !header_text.starts_with("HTTP/1.1") {
-            return Err("Invalid http response!".to_owned());
-        }
-        let mut header_parts = header_lines
-            .next()
-            .ok_or("No more header lines".to_owned())?
-            .split_whitespace();
-        self.response_code = ResponseCode::from_int(
-            header_parts
-                .nth(1)
-                .ok_or("Unable to parse response code".to_owned())?
-                .parse::<u32>()
-                .map_err(|e| e.to_string())?,
-        );
-
-        while let Some(line) = header_lines.next() {
-            let mut line_parts = line.split(": ");
-            if line_parts.clone().collect::<Vec<&str>>().len() < 2 {
-                continue;
-            }
-            if let Some(header) = line_parts.clone().nth(0) {
-                if header.to_lowercase() == "transfer-encoding" {
-                    if let Some(te) = line_parts.clone().nth(1) {
-                        if te.to_lowercase().contains("chunked") {
-                            transfer_encoding = TransferEncoding::Chunked;
-                        }
-                    }
-                }
+    /// Takes the status line and header fields from the events of a `ResponseParser`.
+    /// A status line replaces the head of any interim response before it.
+    pub fn apply_head_event(&mut self, event: &ParseEvent) -> Result<(), Error> {
+        match *event {
+            ParseEvent::Status {
+                ref version,
+                code,
+                ref reason,
+            } => {
+                self.version = version.parse()?;
+                self.response_code = ResponseCode::from_int(u32::from(code));
+                self.reason = reason.clone();
+                self.headers = HeaderMap::new();
             }
-            self.headers.insert(
-                line_parts
-                    .next()
-                    .ok_or("Malformed header".to_owned())?
-                    .to_owned(),
-                line_parts
-                    .next()
-                    .ok_or("Malformed header".to_owned())?
-                    .to_owned(),
-            );
+            ParseEvent::Header {
+                ref name,
+                ref value,
+            } => self.headers.append(name, value),
+            _ => {}
         }
-
-        Ok(transfer_encoding)
+        Ok(())
     }
 
-    fn parse_body(
-        &mut self,
-        data: &mut [u8],
-        transfer_encoding: TransferEncoding,
-        last_chunk_size: Option<usize>,
-    ) -> Result<(), String> {
-        match transfer_encoding {
-            TransferEncoding::Normal => {
-                self.set_body(data);
-                Ok(())
-            }
-            TransferEncoding::Chunked => {
-                if data.to_vec() == "0\r\n\r\n".as_bytes().to_vec() {
-                    return Ok(());
-                }
-                let first_look = usize::min(8, data.len());
-                if let Some(offset) =
-                    find_sub_vector(&data[0..first_look].to_vec(), &"\r\n".as_bytes().to_vec())
-                {
-                    let chunk_len = usize::from_str_radix(
-                        &String::from_utf8(data[0..offset - 2].to_vec()).map_err(|e| {
-                            format!("Error parsing chunked encoding: {}", e.to_string())
-                        })?,
-                        16,
-                    ).map_err(|e| {
-                        format!("Error parsing chunked encoding: {}", e.to_string())
-                    })?;
-                    self.body
-                        .append(&mut data[offset..offset + chunk_len].to_vec());
-                    self.parse_body(
-                        &mut data[offset + chunk_len + 2..],
-                        transfer_encoding,
-                        Some(last_chunk_size.unwrap_or(0) + chunk_len),
-                    )
-                } else {
-                    Err(format!(
-                        "Error parsing chunked data: unable to read chunk size"
-                    ))
-                }
-            }
+    /// Reads a status line and header block, like the one `ResponseParser::head` returns
+    pub fn parse_headers(&mut self, data: &[u8]) -> Result<TransferEncoding, Error> {
+        let mut parser = ResponseParser::new(HttpMethods::Get);
+        let mut events = Vec::new();
+        parser.push(data, &mut events)?;
+        for event in &events {
+            self.apply_head_event(event)?;
+        }
+        match parser.body_length() {
+            Some(BodyLength::Chunked) => Ok(TransferEncoding::Chunked),
+            Some(_) => Ok(TransferEncoding::Normal),
+            None => Err(Error::Parse("Incomplete header block".to_owned())),
         }
     }
 
     /// Returns a Response object from a given stream
-    pub fn from_request(stream: &mut Box<Transport>, request: Request) -> Result<Response, String> {
-        let _ = stream.write(&request.to_payload());
-        let mut data = Vec::new();
-        let _ = stream.make_request(&mut data);
-
-        if let Some(i) = Response::find_body_start(&data.clone()) {
-            let mut response = Response::new();
-            let transfer_encoding = response.parse_headers(&data[0..i - 2])?;
-            response.parse_body(&mut data[i + 1..], transfer_encoding, None)?;
-            return Ok(response);
-        }
-        Err("Could not parse http response!".to_owned())
-    }
-
-    /// Finds the end of the http header block
-    fn find_body_start(data: &[u8]) -> Option<usize> {
-        for i in 3..data.len() {
-            if data[i - 3] == b'\r'
-                && data[i - 2] == b'\n'
-                && data[i - 1] == b'\r'
-                && data[i] == b'\n'
-            {
-                return Some(i);
-            }
-        }
-        None
+    pub fn from_request(stream: Box<Transport>, request: Request) -> Result<Response, Error> {
+        StreamingResponse::from_request(stream, request, None)?.into_response()
     }
 }
+
+#[test]
+fn response_parse_headers() {
+    let head = b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nset-cookie: b=2\r\nLocation:http://example.com/a: b \r\nX-Folded: one\r\n two\r\n\r\n";
+    let mut response = Response::new();
+    response.parse_headers(head).unwrap();
+    assert_eq!(response.headers().get_all("Set-Cookie"), vec!["a=1", "b=2"]);
+    assert_eq!(response.header("location"), Some("http://example.com/a: b".to_owned()));
+    assert_eq!(response.header("x-folded"), Some("one two".to_owned()));
+    assert!(response.parse_headers(b"HTTP/1.1 200 OK\r\nno colon\r\n\r\n").is_err());
+}
+
+#[test]
+fn response_status_line() {
+    let mut response = Response::new();
+    response.parse_headers(b"HTTP/1.0 420 Enhance Your Calm\r\n\r\n").unwrap();
+    assert_eq!(response.version(), HttpVersion::Http10);
+    assert_eq!(response.response_code(), ResponseCode::Other(420));
+    assert_eq!(response.reason(), "Enhance Your Calm");
+
+    let mut response = Response::new();
+    response.parse_headers(b"HTTP/1.1 204\r\n\r\n").unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NoContent);
+    assert_eq!(response.reason(), "");
+    assert!(Response::new().parse_headers(b"HTTP/2.0 200 OK\r\n\r\n").is_err());
+
+    // the final response replaces an interim one
+    let head = b"HTTP/1.1 100 Continue\r\nX-Interim: 1\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+    let mut response = Response::new();
+    assert_eq!(response.parse_headers(head).unwrap(), TransferEncoding::Chunked);
+    assert_eq!(response.response_code(), ResponseCode::Ok);
+    assert!(response.header("x-interim").is_none());
+    assert!(Response::new().parse_headers(b"HTTP/1.1 200 OK\r\n").is_err());
+}
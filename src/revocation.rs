@@ -0,0 +1,275 @@
+extern crate base64;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::{Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+
+use der;
+use error::Error;
+use signature;
+
+/// Starts the message of the rustls error a revoked certificate is reported with
+pub const CERTIFICATE_REVOKED: &str = "Certificate revoked: ";
+
+/// Starts the message of the rustls error a hard failure without a usable list is reported with
+pub const REVOCATION_UNAVAILABLE: &str = "Revocation status unavailable: ";
+
+const PEM_BEGIN: &str = "-----BEGIN X509 CRL-----";
+const PEM_END: &str = "-----END X509 CRL-----";
+
+/// A source of certificate revocation lists
+#[derive(Clone, Debug)]
+pub enum CrlSource {
+    /// A file of one DER list, or of one or more PEM lists
+    Path(PathBuf),
+    /// A single DER encoded list
+    Der(Vec<u8>),
+    /// One or more PEM encoded lists
+    Pem(Vec<u8>),
+}
+
+/// What to do with a certificate whose issuer has no current revocation list that it signed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevocationMode {
+    /// Accept it, only certificates listed as revoked are rejected
+    SoftFail,
+    /// Reject it with `Error::RevocationUnavailable`, since its revocation status is unknown
+    HardFail,
+}
+
+/// The parts of a revocation list that are checked
+struct RevocationList {
+    issuer: Vec<u8>,
+    next_update: Option<SystemTime>,
+    revoked: HashSet<Vec<u8>>,
+    der: Vec<u8>,
+}
+
+impl RevocationList {
+    /// Returns whether the list is signed by the key of `spki`, the contents of a
+    /// subjectPublicKeyInfo
+    fn signed_by(&self, spki: &[u8]) -> bool {
+        der::signed(&self.der).map_or(false, |signed| signature::signed_by(&signed, spki))
+    }
+}
+
+/// Returns the subjectPublicKeyInfo contents of the presented and trusted certificates
+/// named `issuer`, one of which issued the certificate being checked
+fn issuer_keys<'a>(
+    roots: &'a RootCertStore,
+    presented: &'a [Certificate],
+    issuer: &[u8],
+) -> Vec<&'a [u8]> {
+    let mut keys: Vec<&[u8]> = presented
+        .iter()
+        .filter(|cert| der::tbs(&cert.0).map_or(false, |tbs| tbs.subject == issuer))
+        .filter_map(|cert| der::spki_contents(&cert.0))
+        .collect();
+    keys.extend(
+        roots
+            .roots
+            .iter()
+            .map(|root| root.to_trust_anchor())
+            .filter(|anchor| anchor.subject == issuer)
+            .map(|anchor| anchor.spki),
+    );
+    keys
+}
+
+/// Reads the revocation lists of every source
+fn load(sources: &[CrlSource]) -> Result<Vec<RevocationList>, Error> {
+    let mut lists = Vec::new();
+    for source in sources {
+        let ders = match *source {
+            CrlSource::Path(ref path) => {
+                let data = fs::read(path).map_err(|e| {
+                    Error::Certificate(format!("Unable to read {}: {}", path.display(), e))
+                })?;
+                if data.starts_with(b"-----") {
+                    pem_lists(&data)?
+                } else {
+                    vec![data]
+                }
+            }
+            CrlSource::Der(ref der) => vec![der.clone()],
+            CrlSource::Pem(ref pem) => pem_lists(pem)?,
+        };
+        for der in ders {
+            let crl = der::crl(&der)
+                .ok_or_else(|| Error::Certificate("Invalid certificate revocation list".to_owned()))?;
+            lists.push(RevocationList {
+                issuer: crl.issuer.to_vec(),
+                next_update: crl.next_update,
+                revoked: crl.revoked.iter().map(|s| s.to_vec()).collect(),
+                der: der.clone(),
+            });
+        }
+    }
+    Ok(lists)
+}
+
+/// Returns the DER of each list in PEM data
+fn pem_lists(pem: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let text = String::from_utf8_lossy(pem);
+    let mut lists = Vec::new();
+    let mut body: Option<String> = None;
+    for line in text.lines().map(|l| l.trim()) {
+        if line == PEM_BEGIN {
+            body = Some(String::new());
+        } else if line == PEM_END {
+            if let Some(body) = body.take() {
+                let der = base64::decode(&body)
+                    .map_err(|e| Error::Certificate(format!("Invalid PEM revocation list: {}", e)))?;
+                lists.push(der);
+            }
+        } else if let Some(ref mut body) = body {
+            body.push_str(line);
+        }
+    }
+    if lists.is_empty() {
+        return Err(Error::Certificate("No revocation lists found in PEM data".to_owned()));
+    }
+    Ok(lists)
+}
+
+/// Checks the certificates `inner` accepted against local revocation lists.
+///
+/// A list only counts for a certificate when it is signed by the certificate's issuer,
+/// found in the presented chain or among the trust anchors.
+pub struct RevocationVerifier {
+    lists: Vec<RevocationList>,
+    mode: RevocationMode,
+    inner: Arc<dyn ServerCertVerifier>,
+}
+
+impl RevocationVerifier {
+    pub fn new(
+        sources: &[CrlSource],
+        mode: RevocationMode,
+        inner: Arc<dyn ServerCertVerifier>,
+    ) -> Result<RevocationVerifier, Error> {
+        Ok(RevocationVerifier {
+            lists: load(sources)?,
+            mode: mode,
+            inner: inner,
+        })
+    }
+}
+
+impl ServerCertVerifier for RevocationVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented: &[Certificate],
+        dns_name: &str,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        self.inner
+            .verify_server_cert(roots, presented, dns_name, ocsp_response)?;
+        let now = SystemTime::now();
+        for cert in presented {
+            let tbs = der::tbs(&cert.0).ok_or_else(|| {
+                TLSError::General(format!("Unreadable certificate from {}", dns_name))
+            })?;
+            // a self-issued certificate is a trust anchor, nothing can revoke it
+            if tbs.issuer == tbs.subject {
+                continue;
+            }
+            let name = der::subject(&cert.0).unwrap_or_else(|| dns_name.to_owned());
+            let keys = issuer_keys(roots, presented, tbs.issuer);
+            let mut lists = Vec::new();
+            for list in self.lists.iter().filter(|l| l.issuer[..] == tbs.issuer[..]) {
+                if keys.iter().any(|key| list.signed_by(key)) {
+                    lists.push(list);
+                } else {
+                    warn!("Ignoring a revocation list not signed by the issuer of {}", name);
+                }
+            }
+            if lists.iter().any(|l| l.revoked.contains(tbs.serial)) {
+                return Err(TLSError::General(format!("{}{}", CERTIFICATE_REVOKED, name)));
+            }
+            if lists.iter().any(|l| l.next_update.map_or(true, |t| t >= now)) {
+                continue;
+            }
+            match self.mode {
+                RevocationMode::SoftFail => {
+                    debug!("No current revocation list for the issuer of {}", name)
+                }
+                RevocationMode::HardFail => {
+                    return Err(TLSError::General(format!("{}{}", REVOCATION_UNAVAILABLE, name)))
+                }
+            }
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[test]
+fn revocation_lists() {
+    let pem = include_bytes!("../test_server/revoked.crl");
+    let lists = load(&[CrlSource::Pem(pem.to_vec())]).unwrap();
+    assert_eq!(lists.len(), 1);
+    assert!(lists[0].next_update.unwrap() > SystemTime::now());
+
+    let cert = der::pem_to_der(include_bytes!("../test_server/revoked.pem"));
+    let tbs = der::tbs(&cert).unwrap();
+    assert_eq!(&lists[0].issuer[..], tbs.issuer);
+    assert!(lists[0].revoked.contains(tbs.serial));
+
+    let empty = load(&[CrlSource::Pem(include_bytes!("../test_server/empty.crl").to_vec())]).unwrap();
+    assert!(empty[0].revoked.is_empty());
+    assert!(load(&[CrlSource::Pem(b"not a list".to_vec())]).is_err());
+}
+
+#[test]
+fn revocation_list_signatures() {
+    use pinning::StubVerifier;
+
+    let spki = |cert: &[u8]| der::spki_contents(cert).unwrap().to_vec();
+    let issuer = der::pem_to_der(include_bytes!("../test_server/crl_ca.pem"));
+    let other = der::pem_to_der(include_bytes!("../test_server/ca.pem"));
+    let pem = include_bytes!("../test_server/revoked.crl").to_vec();
+    let lists = load(&[CrlSource::Pem(pem.clone())]).unwrap();
+    assert!(lists[0].signed_by(&spki(&issuer)));
+    assert!(!lists[0].signed_by(&spki(&other)));
+
+    // the issuer comes with the chain here, it could be a trust anchor as well
+    let chain = [
+        Certificate(der::pem_to_der(include_bytes!("../test_server/revoked.pem"))),
+        Certificate(issuer),
+    ];
+    let roots = RootCertStore::empty();
+    let verifier = RevocationVerifier::new(
+        &[CrlSource::Pem(pem.clone())],
+        RevocationMode::SoftFail,
+        Arc::new(StubVerifier::Accept),
+    ).unwrap();
+    match verifier.verify_server_cert(&roots, &chain, "127.0.0.1", &[]) {
+        Err(e) => match Error::from(e) {
+            Error::CertificateRevoked(subject) => assert_eq!(subject, "CN=127.0.0.1"),
+            e => panic!("unexpected error: {}", e),
+        },
+        Ok(_) => panic!("expected the certificate to be revoked"),
+    }
+
+    // a list with a broken signature is ignored, leaving the status unknown
+    let mut forged = load(&[CrlSource::Pem(pem)]).unwrap().remove(0);
+    let last = forged.der.len() - 1;
+    forged.der[last] ^= 1;
+    let verifier = RevocationVerifier {
+        lists: vec![forged],
+        mode: RevocationMode::HardFail,
+        inner: Arc::new(StubVerifier::Accept),
+    };
+    match verifier.verify_server_cert(&roots, &chain, "127.0.0.1", &[]) {
+        Err(e) => match Error::from(e) {
+            Error::RevocationUnavailable(subject) => assert_eq!(subject, "CN=127.0.0.1"),
+            e => panic!("unexpected error: {}", e),
+        },
+        Ok(_) => panic!("expected the forged list to be ignored"),
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use rustls::StoresClientSessions;
+
+const MAGIC: &[u8] = b"HSC1";
+
+// tells apart the temporary files of caches saving at the same time
+static SAVES: AtomicUsize = AtomicUsize::new(0);
+
+/// Remembers TLS sessions and tickets so that repeat connections resume them,
+/// optionally keeping them in a file so they survive a restart
+pub struct SessionCache {
+    state: Mutex<CacheState>,
+    path: Option<PathBuf>,
+}
+
+struct CacheState {
+    entries: HashMap<Vec<u8>, Vec<u8>>,
+    // keys from the oldest to the newest, the oldest is evicted first
+    order: VecDeque<Vec<u8>>,
+    capacity: usize,
+}
+
+impl CacheState {
+    fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(key.clone(), value).is_some() {
+            self.order.retain(|k| *k != key);
+        }
+        self.order.push_back(key);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        for key in &self.order {
+            if let Some(value) = self.entries.get(key) {
+                write_field(&mut data, key);
+                write_field(&mut data, value);
+            }
+        }
+        data
+    }
+}
+
+impl SessionCache {
+    /// Returns an in-memory cache holding up to `capacity` sessions
+    pub fn new(capacity: usize) -> SessionCache {
+        SessionCache {
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+                capacity: capacity,
+            }),
+            path: None,
+        }
+    }
+
+    /// Returns a cache that is loaded from `path` and written back whenever a session is added.
+    ///
+    /// A missing or unreadable file starts an empty cache.
+    pub fn with_file<P: AsRef<Path>>(capacity: usize, path: P) -> SessionCache {
+        let mut cache = SessionCache::new(capacity);
+        let path = path.as_ref().to_path_buf();
+        if let Ok(data) = fs::read(&path) {
+            match decode(&data) {
+                Some(entries) => {
+                    if let Ok(mut state) = cache.state.lock() {
+                        for (key, value) in entries {
+                            state.insert(key, value);
+                        }
+                    }
+                }
+                None => warn!("Ignoring the invalid TLS session cache {}", path.display()),
+            }
+        }
+        cache.path = Some(path);
+        cache
+    }
+
+    /// Returns the number of cached sessions
+    pub fn len(&self) -> usize {
+        self.state.lock().map(|s| s.entries.len()).unwrap_or(0)
+    }
+
+    fn save(&self, data: &[u8]) {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return,
+        };
+        // write a temporary file and rename it, so a crash never leaves half a cache,
+        // named so that other caches and processes saving to the same path never share it
+        let temporary = temporary_path(path);
+        let result = open_private(&temporary)
+            .and_then(|mut file| file.write_all(data))
+            .and_then(|_| fs::rename(&temporary, path));
+        if let Err(e) = result {
+            warn!("Unable to save the TLS session cache to {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl StoresClientSessions for SessionCache {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        let data = match self.state.lock() {
+            Ok(mut state) => {
+                state.insert(key, value);
+                state.encode()
+            }
+            Err(_) => return false,
+        };
+        self.save(&data);
+        true
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.state.lock().ok()?.entries.get(key).cloned()
+    }
+}
+
+/// Returns a path next to `path` that no other save uses
+fn temporary_path(path: &Path) -> PathBuf {
+    let save = SAVES.fetch_add(1, Ordering::Relaxed);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}-{}.tmp", process::id(), save));
+    path.with_file_name(name)
+}
+
+/// Opens a file only the current user can read, the sessions hold resumption secrets
+#[cfg(unix)]
+fn open_private(path: &Path) -> ::std::io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn open_private(path: &Path) -> ::std::io::Result<fs::File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+}
+
+fn write_field(data: &mut Vec<u8>, field: &[u8]) {
+    let length = field.len() as u32;
+    data.extend(&[
+        (length >> 24) as u8,
+        (length >> 16) as u8,
+        (length >> 8) as u8,
+        length as u8,
+    ]);
+    data.extend(field);
+}
+
+fn read_field(data: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    if data.len() < 4 {
+        return None;
+    }
+    let length = data[..4]
+        .iter()
+        .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+    let end = 4usize.checked_add(length)?;
+    let field = data.get(4..end)?;
+    Some((field.to_vec(), &data[end..]))
+}
+
+fn decode(data: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    if !data.starts_with(MAGIC) {
+        return None;
+    }
+    let mut rest = &data[MAGIC.len()..];
+    let mut entries = Vec::new();
+    while !rest.is_empty() {
+        let (key, next) = read_field(rest)?;
+        let (value, next) = read_field(next)?;
+        entries.push((key, value));
+        rest = next;
+    }
+    Some(entries)
+}
+
+#[test]
+fn session_cache_evicts_oldest() {
+    let cache = SessionCache::new(2);
+    cache.put(b"a".to_vec(), b"1".to_vec());
+    cache.put(b"b".to_vec(), b"2".to_vec());
+    cache.put(b"a".to_vec(), b"3".to_vec());
+    cache.put(b"c".to_vec(), b"4".to_vec());
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(b"a"), Some(b"3".to_vec()));
+    assert_eq!(cache.get(b"b"), None);
+
+    let disabled = SessionCache::new(0);
+    disabled.put(b"a".to_vec(), b"1".to_vec());
+    assert_eq!(disabled.get(b"a"), None);
+}
+
+#[test]
+fn session_cache_file() {
+    use std::env;
+
+    let path = temporary_path(&env::temp_dir().join("hasty-sessions"));
+    let cache = SessionCache::with_file(8, &path);
+    cache.put(b"ticket".to_vec(), vec![0u8; 300]);
+    cache.put(b"other".to_vec(), b"value".to_vec());
+
+    let restored = SessionCache::with_file(8, &path);
+    assert_eq!(restored.len(), 2);
+    assert_eq!(restored.get(b"ticket"), Some(vec![0u8; 300]));
+
+    assert!(temporary_path(&path) != temporary_path(&path));
+    fs::write(&path, b"garbage").unwrap();
+    assert_eq!(SessionCache::with_file(8, &path).len(), 0);
+    fs::remove_file(&path).unwrap();
+
+    // a field claiming more than the data holds ends the cache
+    assert_eq!(read_field(&[0xff, 0xff, 0xff, 0xff, 1]), None);
+}
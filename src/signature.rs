@@ -0,0 +1,63 @@
+extern crate ring;
+extern crate untrusted;
+
+use self::ring::signature::{self as algorithms, VerificationAlgorithm};
+
+use der::{self, Signed};
+
+// the signature algorithms accepted, as in RFC 4055 and RFC 5758
+const SHA1_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x05];
+const SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+
+/// Returns whether a certificate or revocation list was signed with the key of `spki`,
+/// the contents of a subjectPublicKeyInfo
+pub fn signed_by(signed: &Signed, spki: &[u8]) -> bool {
+    let key = match der::public_key(spki) {
+        Some(key) => key,
+        None => return false,
+    };
+    let algorithm = match verification_algorithm(signed.algorithm, key.parameter) {
+        Some(algorithm) => algorithm,
+        None => return false,
+    };
+    algorithms::verify(
+        algorithm,
+        untrusted::Input::from(key.key),
+        untrusted::Input::from(signed.data),
+        untrusted::Input::from(signed.signature),
+    ).is_ok()
+}
+
+/// Returns how to check a signature, given its algorithm and the curve of an EC key
+fn verification_algorithm(
+    signature_algorithm: &[u8],
+    curve: Option<&[u8]>,
+) -> Option<&'static dyn VerificationAlgorithm> {
+    match (signature_algorithm, curve) {
+        (SHA1_WITH_RSA, _) => Some(&algorithms::RSA_PKCS1_2048_8192_SHA1),
+        (SHA256_WITH_RSA, _) => Some(&algorithms::RSA_PKCS1_2048_8192_SHA256),
+        (SHA384_WITH_RSA, _) => Some(&algorithms::RSA_PKCS1_2048_8192_SHA384),
+        (SHA512_WITH_RSA, _) => Some(&algorithms::RSA_PKCS1_2048_8192_SHA512),
+        (ECDSA_WITH_SHA256, Some(P256)) => Some(&algorithms::ECDSA_P256_SHA256_ASN1),
+        (ECDSA_WITH_SHA256, Some(P384)) => Some(&algorithms::ECDSA_P384_SHA256_ASN1),
+        (ECDSA_WITH_SHA384, Some(P256)) => Some(&algorithms::ECDSA_P256_SHA384_ASN1),
+        (ECDSA_WITH_SHA384, Some(P384)) => Some(&algorithms::ECDSA_P384_SHA384_ASN1),
+        _ => None,
+    }
+}
+
+#[test]
+fn signature_of_certificates() {
+    let ca = der::pem_to_der(include_bytes!("../test_server/crl_ca.pem"));
+    let other = der::pem_to_der(include_bytes!("../test_server/ca.pem"));
+    let cert = der::pem_to_der(include_bytes!("../test_server/revoked.pem"));
+    let signed = der::signed(&cert).unwrap();
+    assert!(signed_by(&signed, der::spki_contents(&ca).unwrap()));
+    assert!(!signed_by(&signed, der::spki_contents(&other).unwrap()));
+}
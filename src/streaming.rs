@@ -0,0 +1,282 @@
+use std::io::{self, Error as IoError, ErrorKind, Read, Write};
+
+use url::Url;
+
+use constants::{HttpVersion, ResponseCode};
+use error::Error;
+use headers::HeaderMap;
+use parser::{BodyLength, ParseEvent, ResponseParser};
+use request::Request;
+use pool::PoolSlot;
+use response::Response;
+use tls_info::TlsInfo;
+use transports::Transport;
+
+const READ_SIZE: usize = 16 * 1024;
+const DISCARD_LIMIT: u64 = 64 * 1024;
+
+/// A response whose headers have been read, with the body left on the connection
+pub struct StreamingResponse {
+    head: Response,
+    body: BodyReader,
+}
+
+/// Reads a response body straight off the connection, decoding any chunked framing
+pub struct BodyReader {
+    transport: Option<Box<Transport>>,
+    parser: ResponseParser,
+    buffer: Vec<u8>,
+    decoded: Vec<u8>,
+    position: usize,
+    trailers: HeaderMap,
+    keep_alive: bool,
+    slot: Option<PoolSlot>,
+}
+
+impl StreamingResponse {
+    /// Sends a request and reads the response up to the end of its headers.
+    ///
+    /// When a pool slot is given, the connection is returned to it once the
+    /// body has been read, if the response framing allows the connection to be reused.
+    pub fn from_request(
+        mut stream: Box<Transport>,
+        request: Request,
+        slot: Option<PoolSlot>,
+    ) -> Result<StreamingResponse, Error> {
+        let mut parser = ResponseParser::new(request.method());
+        let mut keep_alive = !has_option(request.headers(), "close");
+        request.write_to(&mut *stream)?;
+        stream.flush()?;
+
+        let mut head = Response::new();
+        let mut decoded = Vec::new();
+        let mut trailers = HeaderMap::new();
+        let mut buf = vec![0u8; READ_SIZE];
+        let mut received = 0;
+        while parser.head().is_none() {
+            let read = stream.read(&mut buf)?;
+            if read == 0 {
+                if received == 0 {
+                    return Err(Error::Io(IoError::new(
+                        ErrorKind::UnexpectedEof,
+                        "Connection closed before a response was received",
+                    )));
+                }
+                parser.finish(&mut Vec::new())?;
+            }
+            received += read;
+            let used = decode(
+                &mut parser,
+                &buf[..read],
+                &mut decoded,
+                &mut trailers,
+                Some(&mut head),
+            )?;
+            if used < read {
+                // more data than the response holds, the connection is out of step
+                keep_alive = false;
+            }
+        }
+
+        head.set_tls_info(stream.tls_info());
+        // an HTTP/1.0 server only keeps the connection open when it says so
+        let persistent = match head.version() {
+            HttpVersion::Http10 => has_option(head.headers(), "keep-alive"),
+            HttpVersion::Http11 => !has_option(head.headers(), "close"),
+        };
+        if !persistent || parser.body_length() == Some(BodyLength::UntilClose) {
+            keep_alive = false;
+        }
+
+        let mut body = BodyReader {
+            transport: Some(stream),
+            parser: parser,
+            buffer: buf,
+            decoded: decoded,
+            position: 0,
+            trailers: trailers,
+            keep_alive: keep_alive,
+            slot: slot,
+        };
+        body.release();
+        Ok(StreamingResponse {
+            head: head,
+            body: body,
+        })
+    }
+
+    /// Returns the response code
+    pub fn response_code(&self) -> ResponseCode {
+        self.head.response_code()
+    }
+
+    /// Returns the reason phrase of the status line
+    pub fn reason(&self) -> &str {
+        self.head.reason()
+    }
+
+    /// Returns the protocol version of the status line
+    pub fn version(&self) -> HttpVersion {
+        self.head.version()
+    }
+
+    /// Returns the headers, in the order they were received
+    pub fn headers(&self) -> &HeaderMap {
+        self.head.headers()
+    }
+
+    /// Returns an option of a specific header
+    pub fn get_header(&mut self, name: String) -> Option<String> {
+        self.head.get_header(name)
+    }
+
+    /// Returns the first value of a header, matching the name case-insensitively
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.head.header(name)
+    }
+
+    /// Returns the effective url, after any redirects were followed
+    pub fn url(&self) -> Option<Url> {
+        self.head.url()
+    }
+
+    /// Returns the details of the TLS session, None for plain http
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.head.tls_info()
+    }
+
+    /// Returns the urls that redirected to this response, in the order they were visited
+    pub fn redirects(&self) -> Vec<Url> {
+        self.head.redirects()
+    }
+
+    /// Records where the response came from and the redirects that led to it
+    pub fn set_location(&mut self, url: Url, redirects: Vec<Url>) {
+        self.head.set_url(url);
+        self.head.set_redirects(redirects);
+    }
+
+    /// Reads and throws away a short body so the connection can be reused,
+    /// a longer one is left for the connection to be closed
+    pub fn discard_body(mut self) {
+        let _ = io::copy(&mut self.body.by_ref().take(DISCARD_LIMIT), &mut io::sink());
+    }
+
+    /// Returns the body reader
+    pub fn body(&mut self) -> &mut BodyReader {
+        &mut self.body
+    }
+
+    /// Consumes the response, returning only the body reader
+    pub fn into_body(self) -> BodyReader {
+        self.body
+    }
+
+    /// Reads the rest of the body into memory and returns a buffered `Response`
+    pub fn into_response(mut self) -> Result<Response, Error> {
+        let mut body = Vec::new();
+        self.body.read_to_end(&mut body)?;
+        self.head.set_body(&body);
+        self.head.set_trailers(self.body.trailers.clone());
+        Ok(self.head)
+    }
+}
+
+impl BodyReader {
+    /// Returns true once the whole body has been read
+    pub fn is_finished(&self) -> bool {
+        self.parser.is_complete() && self.position == self.decoded.len()
+    }
+
+    /// Returns the trailer fields sent after a chunked body, known once the body has been read
+    pub fn trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+
+    /// Hands the connection back to the pool once the response is complete
+    fn release(&mut self) {
+        if !self.parser.is_complete() || !self.keep_alive {
+            return;
+        }
+        if let (Some(slot), Some(transport)) = (self.slot.take(), self.transport.take()) {
+            slot.pool.checkin(slot.key, transport);
+        }
+    }
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        while self.position == self.decoded.len() {
+            if self.parser.is_complete() {
+                self.release();
+                return Ok(0);
+            }
+            self.decoded.clear();
+            self.position = 0;
+
+            let read = match self.transport {
+                Some(ref mut transport) => match transport.read(&mut self.buffer) {
+                    Ok(n) => n,
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                },
+                None => 0,
+            };
+            if read == 0 {
+                self.parser.finish(&mut Vec::new())?;
+                return Ok(0);
+            }
+            let used = decode(
+                &mut self.parser,
+                &self.buffer[..read],
+                &mut self.decoded,
+                &mut self.trailers,
+                None,
+            )?;
+            if used < read {
+                self.keep_alive = false;
+            }
+        }
+
+        let n = usize::min(buf.len(), self.decoded.len() - self.position);
+        buf[..n].copy_from_slice(&self.decoded[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Pushes bytes to the parser, appending the body bytes among them to `decoded`
+/// and collecting any trailers
+fn decode(
+    parser: &mut ResponseParser,
+    data: &[u8],
+    decoded: &mut Vec<u8>,
+    trailers: &mut HeaderMap,
+    mut head: Option<&mut Response>,
+) -> Result<usize, Error> {
+    let mut events = Vec::new();
+    let used = parser.push(data, &mut events)?;
+    for event in events {
+        match event {
+            ParseEvent::Body(bytes) => decoded.extend_from_slice(&bytes),
+            ParseEvent::Trailer { name, value } => {
+                trailers.append(&name, &value);
+            }
+            event => {
+                if let Some(ref mut head) = head {
+                    head.apply_head_event(&event)?;
+                }
+            }
+        }
+    }
+    Ok(used)
+}
+
+/// Returns true if the Connection header lists `option`, like `close`
+fn has_option(headers: &HeaderMap, option: &str) -> bool {
+    headers
+        .get_all("connection")
+        .iter()
+        .flat_map(|value| value.split(','))
+        .any(|o| o.trim().eq_ignore_ascii_case(option))
+}
@@ -0,0 +1,123 @@
+extern crate ring;
+
+use std::time::SystemTime;
+
+use der;
+
+/// Details of the TLS session a response was received over
+#[derive(Clone, Debug)]
+pub struct TlsInfo {
+    protocol_version: String,
+    cipher_suite: String,
+    alpn_protocol: Option<String>,
+    peer_certificates: Vec<PeerCertificate>,
+}
+
+impl TlsInfo {
+    pub fn new(
+        protocol_version: String,
+        cipher_suite: String,
+        alpn_protocol: Option<String>,
+        peer_certificates: Vec<PeerCertificate>,
+    ) -> TlsInfo {
+        TlsInfo {
+            protocol_version: protocol_version,
+            cipher_suite: cipher_suite,
+            alpn_protocol: alpn_protocol,
+            peer_certificates: peer_certificates,
+        }
+    }
+
+    /// Returns the negotiated protocol version, like `TLSv1_2`
+    pub fn protocol_version(&self) -> &str {
+        &self.protocol_version
+    }
+
+    /// Returns the negotiated cipher suite, like `TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256`
+    pub fn cipher_suite(&self) -> &str {
+        &self.cipher_suite
+    }
+
+    /// Returns the protocol agreed on with ALPN, if any
+    pub fn alpn_protocol(&self) -> Option<&str> {
+        self.alpn_protocol.as_ref().map(|p| p.as_str())
+    }
+
+    /// Returns the certificates the server presented, starting with its own
+    pub fn peer_certificates(&self) -> &[PeerCertificate] {
+        &self.peer_certificates
+    }
+}
+
+/// A certificate presented by the server
+///
+/// # Example
+///
+/// ```
+/// # extern crate base64;
+/// # extern crate hasty;
+/// use hasty::PeerCertificate;
+///
+/// # fn main() {
+/// let pem = String::from_utf8(include_bytes!("../test_server/https.pem").to_vec()).unwrap();
+/// let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+/// let cert = PeerCertificate::new(base64::decode(&body).unwrap());
+/// assert_eq!(cert.subject().unwrap(), "C=AU, ST=Some-State, O=Internet Widgits Pty Ltd, CN=localhost");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PeerCertificate {
+    der: Vec<u8>,
+}
+
+impl PeerCertificate {
+    pub fn new(der: Vec<u8>) -> PeerCertificate {
+        PeerCertificate { der: der }
+    }
+
+    /// Returns the DER encoding of the certificate
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Returns the subject, formatted like `C=AU, O=Example, CN=localhost`
+    pub fn subject(&self) -> Option<String> {
+        der::subject(&self.der)
+    }
+
+    /// Returns the issuer, formatted like the subject
+    pub fn issuer(&self) -> Option<String> {
+        der::issuer(&self.der)
+    }
+
+    /// Returns the time the certificate becomes valid
+    pub fn not_before(&self) -> Option<SystemTime> {
+        der::validity(&self.der).map(|v| v.0)
+    }
+
+    /// Returns the time the certificate expires
+    pub fn not_after(&self) -> Option<SystemTime> {
+        der::validity(&self.der).map(|v| v.1)
+    }
+
+    /// Returns the SHA-256 fingerprint as colon separated hex, like `AB:CD:...`
+    pub fn sha256_fingerprint(&self) -> String {
+        ring::digest::digest(&ring::digest::SHA256, &self.der)
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<String>>()
+            .join(":")
+    }
+}
+
+#[test]
+fn peer_certificate_fingerprint() {
+    let cert = PeerCertificate::new(der::pem_to_der(include_bytes!("../test_server/https.pem")));
+    assert_eq!(
+        cert.sha256_fingerprint(),
+        "ED:94:8B:98:2D:FB:89:C5:9F:EC:DA:74:0F:B4:06:D8:35:11:48:5E:91:7B:14:3A:7C:6F:E9:64:23:40:89:AE"
+    );
+    assert_eq!(cert.issuer(), cert.subject());
+    assert!(cert.not_before().unwrap() < cert.not_after().unwrap());
+}
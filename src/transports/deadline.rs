@@ -0,0 +1,173 @@
+use std::io::{Error as IoError, ErrorKind};
+use std::time::{Duration, Instant};
+
+use config::Config;
+use error::Error;
+
+/// The time limits that apply to the reads and writes of one request
+#[derive(Clone, Debug)]
+pub struct Deadline {
+    read: Option<Duration>,
+    write: Option<Duration>,
+    until: Option<Instant>,
+    low_speed: Option<(u64, Duration)>,
+    window_start: Instant,
+    window_bytes: u64,
+    window_limited: bool,
+}
+
+impl Deadline {
+    /// Returns limits without any timeouts
+    pub fn none() -> Deadline {
+        Deadline {
+            read: None,
+            write: None,
+            until: None,
+            low_speed: None,
+            window_start: Instant::now(),
+            window_bytes: 0,
+            window_limited: false,
+        }
+    }
+
+    /// Returns the limits configured in `config`, with the request ending by `until`
+    pub fn new(config: &Config, until: Option<Instant>) -> Deadline {
+        Deadline {
+            read: config.get_read_timeout(),
+            write: config.get_write_timeout(),
+            until: until,
+            low_speed: config.get_low_speed_limit(),
+            ..Deadline::none()
+        }
+    }
+
+    /// Returns how long a connect may take, given the configured connect timeout
+    pub fn connect_timeout(&self, connect: Option<Duration>) -> Result<Option<Duration>, Error> {
+        Ok(shortest(connect, self.remaining()?))
+    }
+
+    /// Returns how long the next read may wait for data
+    pub fn read_timeout(&mut self) -> Result<Option<Duration>, Error> {
+        self.check_speed()?;
+        let timeout = shortest(self.read, self.remaining()?);
+        let window = self.window_remaining();
+        self.window_limited = match (window, timeout) {
+            (Some(w), Some(t)) => w < t,
+            (Some(_), None) => true,
+            _ => false,
+        };
+        Ok(shortest(timeout, window))
+    }
+
+    /// Returns how long the next write may wait for the socket
+    pub fn write_timeout(&mut self) -> Result<Option<Duration>, Error> {
+        self.check_speed()?;
+        Ok(shortest(self.write, self.remaining()?))
+    }
+
+    /// Records bytes moved over the connection
+    pub fn progress(&mut self, bytes: usize) -> Result<(), Error> {
+        self.window_bytes += bytes as u64;
+        self.check_speed()
+    }
+
+    /// Called when a wait ran out, fails unless only the low speed window needed checking
+    pub fn timed_out(&mut self) -> Result<(), Error> {
+        if self.window_limited {
+            self.window_limited = false;
+            self.check_speed()
+        } else {
+            Err(Error::Timeout)
+        }
+    }
+
+    fn remaining(&self) -> Result<Option<Duration>, Error> {
+        match self.until {
+            Some(until) => {
+                let now = Instant::now();
+                if now >= until {
+                    Err(Error::Timeout)
+                } else {
+                    Ok(Some(until - now))
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn window_remaining(&self) -> Option<Duration> {
+        self.low_speed.map(|(_, time)| {
+            let elapsed = self.window_start.elapsed();
+            if elapsed >= time {
+                Duration::from_millis(1)
+            } else {
+                time - elapsed
+            }
+        })
+    }
+
+    fn check_speed(&mut self) -> Result<(), Error> {
+        if let Some((limit, time)) = self.low_speed {
+            if self.window_start.elapsed() >= time {
+                let seconds = time.as_secs() as f64 + f64::from(time.subsec_nanos()) / 1e9;
+                if (self.window_bytes as f64) < limit as f64 * seconds {
+                    debug!(
+                        "Transfer too slow: {} bytes in {:?}, the limit is {} bytes/sec",
+                        self.window_bytes, time, limit
+                    );
+                    return Err(Error::Timeout);
+                }
+                self.window_start = Instant::now();
+                self.window_bytes = 0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if a socket error means its timeout ran out
+pub fn is_timeout(e: &IoError) -> bool {
+    e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut
+}
+
+fn shortest(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a < b { a } else { b }),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+#[test]
+fn deadline_expired() {
+    let mut deadline = Deadline::none();
+    deadline.until = Some(Instant::now());
+    match deadline.read_timeout() {
+        Err(Error::Timeout) => {}
+        _ => panic!("expected a timeout"),
+    }
+}
+
+#[test]
+fn deadline_shortest_wins() {
+    let mut deadline = Deadline::none();
+    deadline.read = Some(Duration::from_secs(5));
+    deadline.until = Some(Instant::now() + Duration::from_secs(60));
+    assert_eq!(deadline.read_timeout().unwrap(), Some(Duration::from_secs(5)));
+    assert!(deadline.timed_out().is_err());
+}
+
+#[test]
+fn deadline_low_speed() {
+    let mut deadline = Deadline::none();
+    deadline.low_speed = Some((100_000, Duration::from_millis(10)));
+    deadline.progress(500).unwrap();
+    ::std::thread::sleep(Duration::from_millis(20));
+    assert!(deadline.progress(1).is_err());
+
+    let mut deadline = Deadline::none();
+    deadline.low_speed = Some((100_000, Duration::from_millis(10)));
+    deadline.progress(5000).unwrap();
+    ::std::thread::sleep(Duration::from_millis(20));
+    assert!(deadline.progress(1).is_ok());
+}
@@ -1,18 +1,25 @@
-use std::io::{Read, Write, Result as IoResult, Error as IoError};
+use std::io::{Read, Write, Result as IoResult};
 use std::net::TcpStream;
 
-use transports::Transport;
+use transports::{connect_socket, is_timeout, socket_is_stale, Deadline, Transport};
 use url::Host;
 use config::Config;
+use error::Error;
 
 pub struct HttpTransport {
     stream: TcpStream,
+    deadline: Deadline,
 }
 
 impl HttpTransport {
-    pub fn new(host: Option<Host<&str>>, port: Option<u16>, _: &Config) -> Result<Box<Transport>, String> {
+    pub fn new(
+        host: Option<Host<&str>>,
+        port: Option<u16>,
+        config: &Config,
+        deadline: Deadline,
+    ) -> Result<Box<Transport>, Error> {
         if let None = host {
-            return Err("Invalid host!".to_owned());
+            return Err(Error::InvalidUrl("Invalid host!".to_owned()));
         }
 
         let port = match port {
@@ -20,28 +27,58 @@ impl HttpTransport {
             None => 80,
         };
 
+        let host = host.unwrap().to_string();
+        // a forward proxy takes the request in absolute-form and connects for us
+        let stream = match config.proxy_for(&host) {
+            Some(proxy) => connect_socket(proxy.host(), proxy.port(), config, &deadline)?,
+            None => connect_socket(&host, port, config, &deadline)?,
+        };
+
         Ok(Box::new(Transport::Http(HttpTransport {
-            stream: TcpStream::connect(format!("{}:{}", host.unwrap(), port)).map_err(|e| e.to_string())?,
+            stream: stream,
+            deadline: deadline,
         })))
     }
 
-    pub fn make_request(&mut self, data: &mut Vec<u8>) -> Result<usize, IoError> {
-        self.stream.read_to_end(data)
+    pub fn is_stale(&mut self) -> bool {
+        socket_is_stale(&self.stream)
+    }
+
+    pub fn set_deadline(&mut self, deadline: Deadline) {
+        self.deadline = deadline;
     }
 }
 
 impl Read for HttpTransport {
     fn read(&mut self, mut buf: &mut [u8]) -> IoResult<usize> {
-        self.stream.read(&mut buf)
+        loop {
+            self.stream.set_read_timeout(self.deadline.read_timeout()?)?;
+            match self.stream.read(&mut buf) {
+                Ok(n) => {
+                    self.deadline.progress(n)?;
+                    return Ok(n);
+                }
+                Err(ref e) if is_timeout(e) => self.deadline.timed_out()?,
+                Err(e) => return Err(e),
+            }
+        }
     }
 }
 
 impl Write for HttpTransport {
     fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
-        self.stream.write(buf)
+        self.stream.set_write_timeout(self.deadline.write_timeout()?)?;
+        match self.stream.write(buf) {
+            Ok(n) => {
+                self.deadline.progress(n)?;
+                Ok(n)
+            }
+            Err(ref e) if is_timeout(e) => Err(Error::Timeout.into()),
+            Err(e) => Err(e),
+        }
     }
 
     fn flush(&mut self) -> IoResult<()> {
         self.stream.flush()
     }
-}
\ No newline at end of file
+}
@@ -1,36 +1,24 @@
-extern crate webpki;
-
-use std::io::{Error as IoError, Read, Write};
+use std::io::{Error as IoError, ErrorKind, Read, Write};
 use std::net::{Shutdown, TcpStream};
-use std::sync::Arc;
-use std::time::Duration;
 
 use config::Config;
-use rustls::{
-    Certificate, ClientConfig, ClientSession, RootCertStore, ServerCertVerified,
-    ServerCertVerifier, Session, TLSError,
-};
-use transports::{find_sub_vector, Transport};
-use url::Host;
-
-pub struct UnsafeCertVerifier {}
-
-impl ServerCertVerifier for UnsafeCertVerifier {
-    fn verify_server_cert(
-        &self,
-        _: &RootCertStore,
-        _: &[Certificate],
-        _: &str,
-        //        _: webpki::DNSNameRef,
-        _: &[u8],
-    ) -> Result<ServerCertVerified, TLSError> {
-        Ok(ServerCertVerified::assertion())
-    }
-}
+use constants::HttpMethods;
+use error::Error;
+use parser::ResponseParser;
+use proxy::Proxy;
+use request::Request;
+use response::Response;
+use tls_info::{PeerCertificate, TlsInfo};
+use rustls::{ClientSession, Session};
+use transports::tls::TlsConfigs;
+use transports::{connect_socket, is_timeout, socket_is_stale, Deadline, Transport};
+use url::{Host, Url};
 
 pub struct HttpsTransport {
     socket: TcpStream,
     session: ClientSession,
+    closed: bool,
+    deadline: Deadline,
 }
 
 impl HttpsTransport {
@@ -38,147 +26,269 @@ impl HttpsTransport {
         host: Option<Host<&str>>,
         port: Option<u16>,
         config: &Config,
-    ) -> Result<Box<Transport>, String> {
+        mut deadline: Deadline,
+        server_name: Option<String>,
+        tls: &TlsConfigs,
+    ) -> Result<Box<Transport>, Error> {
         if let None = host {
-            return Err("Invalid host!".to_owned());
+            return Err(Error::InvalidUrl("Invalid host!".to_owned()));
         }
-        let host = host.unwrap().to_string();
+        // an ip address is checked against the certificate without brackets
+        let (host, host_name) = match host.unwrap() {
+            Host::Domain(domain) => (domain.to_owned(), domain.to_owned()),
+            Host::Ipv4(ip) => (ip.to_string(), ip.to_string()),
+            Host::Ipv6(ip) => (format!("[{}]", ip), ip.to_string()),
+        };
+        let server_name = server_name
+            .map(|n| n.trim_matches(|c| c == '[' || c == ']').to_owned())
+            .unwrap_or(host_name);
 
         let port = match port {
             Some(p) => p,
             None => 443,
         };
 
-        let mut tls = ClientConfig::new();
-        if config.allows_insecure_https() {
-            tls.dangerous()
-                .set_certificate_verifier(Arc::new(UnsafeCertVerifier {}));
-        }
-
-        /* TODO: support requests by IP
-        match webpki::DNSNameRef::try_from_ascii_str(&host) {
-            Ok(host_dnsname) => {
-                let t = HttpsTransport {
-                    socket: TcpStream::connect(format!("{}:{:?}", host, port)).map_err(|e| e.to_string())?,
-                    session: ClientSession::new(&Arc::new(tls), host_dnsname)
-                };
-                Ok(Box::new(Transport::Https(t)))
+        let socket = match config.proxy_for(&host) {
+            Some(proxy) => {
+                let mut socket = connect_socket(proxy.host(), proxy.port(), config, &deadline)?;
+                open_tunnel(&mut socket, proxy, &host, port, &mut deadline)?;
+                socket
             }
-            Err(()) => Err(format!("Invalid hostname: {}", host))
-        }
-        */
+            None => connect_socket(&host, port, config, &deadline)?,
+        };
 
         let t = HttpsTransport {
-            socket: TcpStream::connect(format!("{}:{:?}", host, port))
-                .map_err(|e| e.to_string())?,
-            session: ClientSession::new(&Arc::new(tls), &host),
+            socket: socket,
+            session: ClientSession::new(&tls.for_server(&server_name), &server_name),
+            closed: false,
+            deadline: deadline,
         };
         Ok(Box::new(Transport::Https(t)))
     }
 
-    pub fn make_request(&mut self, data: &mut Vec<u8>) -> Result<usize, IoError> {
-        let mut chunked_encoding = false;
+    /// Writes every pending tls record to the socket
+    fn write_records(&mut self) -> Result<(), Error> {
+        while self.session.wants_write() {
+            self.socket.set_write_timeout(self.deadline.write_timeout()?)?;
+            match self.session.write_tls(&mut self.socket) {
+                Ok(n) => self.deadline.progress(n)?,
+                Err(ref e) if is_timeout(e) => return Err(Error::Timeout),
+                Err(e) => {
+                    warn!("Error writing to TLS stream: {}", e.to_string());
+                    return Err(Error::Io(e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks until tls records arrive or the server closes the connection,
+    /// and processes them
+    fn read_records(&mut self) -> Result<(), Error> {
         loop {
-            if self.session.wants_read() && self.socket_is_ready_to_read() {
-                match self.session.read_tls(&mut self.socket) {
-                    Ok(_) => {}
-                    Err(e) => warn!("Error reading TLS stream: {}", e.to_string()),
+            self.socket.set_read_timeout(self.deadline.read_timeout()?)?;
+            match self.session.read_tls(&mut self.socket) {
+                Ok(0) => {
+                    self.closed = true;
+                    return Ok(());
                 }
-                match self.session.process_new_packets() {
-                    Ok(_) => {}
-                    Err(e) => warn!("Error processing TLS packets: {}", e.to_string()),
+                Ok(n) => {
+                    self.deadline.progress(n)?;
+                    break;
                 }
-
-                let mut tmp_data = Vec::new();
-                match self.session.read_to_end(&mut tmp_data) {
-                    Ok(recv_bytes) => {
-                        if recv_bytes == 0 {
-                            continue;
-                        }
-
-                        if chunked_encoding {
-                            if tmp_data.ends_with("0\r\n\r\n".as_bytes()) {
-                                data.append(&mut tmp_data);
-                                break;
-                            } else {
-                                data.append(&mut tmp_data);
-                            }
-                        } else {
-                            if let Some(header_length) =
-                                find_sub_vector(&tmp_data, &"\r\n\r\n".as_bytes().to_vec())
-                            {
-                                match String::from_utf8(tmp_data[0..header_length].to_vec()) {
-                                    Ok(h) => {
-                                        // TODO: make this smarter
-                                        if h.to_lowercase().contains("transfer-encoding: chunked") {
-                                            chunked_encoding = true;
-                                        }
-                                        data.append(&mut tmp_data);
-                                        if !chunked_encoding {
-                                            break;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        data.append(&mut tmp_data);
-                                        trace!("Can't parse headers {}", e.to_string());
-                                        break;
-                                    }
-                                }
-                            } else {
-                                trace!("Cannot find headers in response!");
-                                break;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        /* handle this */
-                        warn!("Error: {}", e.to_string());
-                        break;
-                    }
+                Err(ref e) if is_timeout(e) => self.deadline.timed_out()?,
+                Err(e) => {
+                    warn!("Error reading TLS stream: {}", e.to_string());
+                    return Err(Error::Io(e));
                 }
             }
-            if self.session.wants_write() {
-                match self.session.write_tls(&mut self.socket) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        warn!("Error writing to TLS stream: {}", e.to_string());
-                        break;
-                    }
-                }
+        }
+        if let Err(e) = self.session.process_new_packets() {
+            warn!("Error processing TLS packets: {}", e.to_string());
+            // tell the server why with the alert rustls queued, if it still listens
+            let _ = self.session.write_tls(&mut self.socket);
+            return Err(Error::from(e));
+        }
+        Ok(())
+    }
+
+    fn complete_handshake(&mut self) -> Result<(), Error> {
+        while self.session.is_handshaking() {
+            self.write_records()?;
+            if self.closed {
+                return Err(Error::Io(IoError::new(
+                    ErrorKind::UnexpectedEof,
+                    "Connection closed during TLS handshake",
+                )));
             }
+            self.read_records()?;
         }
+        Ok(())
+    }
+
+    /// Returns the details of the session, once the handshake is done
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        if self.session.is_handshaking() {
+            return None;
+        }
+        let certificates = self
+            .session
+            .get_peer_certificates()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| PeerCertificate::new(c.0))
+            .collect();
+        Some(TlsInfo::new(
+            self.session
+                .get_protocol_version()
+                .map(|v| format!("{:?}", v))
+                .unwrap_or_default(),
+            self.session
+                .get_negotiated_ciphersuite()
+                .map(|s| format!("{:?}", s.suite))
+                .unwrap_or_default(),
+            self.session.get_alpn_protocol().map(|p| p.to_owned()),
+            certificates,
+        ))
+    }
+
+    pub fn is_stale(&mut self) -> bool {
+        self.closed || socket_is_stale(&self.socket)
+    }
+
+    pub fn set_deadline(&mut self, deadline: Deadline) {
+        self.deadline = deadline;
+    }
+}
+
+/// Asks the proxy for a tunnel to `host`, the socket then carries the tls session
+fn open_tunnel(
+    socket: &mut TcpStream,
+    proxy: &Proxy,
+    host: &str,
+    port: u16,
+    deadline: &mut Deadline,
+) -> Result<(), Error> {
+    let url: Url = format!("https://{}:{}/", host, port)
+        .parse()
+        .map_err(|e| Error::InvalidUrl(format!("{}: {}", host, e)))?;
+    let mut connect = Request::from_url(url).with_method(HttpMethods::Connect);
+    if let Some(authorization) = proxy.authorization() {
+        connect.add_raw_header("Proxy-Authorization".to_owned(), authorization);
+    }
+    socket.set_write_timeout(deadline.write_timeout()?)?;
+    match connect.write_to(socket) {
+        Err(Error::Io(ref e)) if is_timeout(e) => return Err(Error::Timeout),
+        result => result?,
+    }
+
+    // read a byte at a time, anything after the head belongs to the tls session
+    let mut parser = ResponseParser::new(HttpMethods::Connect);
+    let mut events = Vec::new();
+    let mut byte = [0u8; 1];
+    while parser.head().is_none() {
+        socket.set_read_timeout(deadline.read_timeout()?)?;
+        match socket.read(&mut byte) {
+            Ok(0) => {
+                return Err(Error::Proxy(format!(
+                    "{}:{} closed the connection during CONNECT",
+                    proxy.host(),
+                    proxy.port()
+                )))
+            }
+            Ok(n) => {
+                deadline.progress(n)?;
+                parser.push(&byte[..n], &mut events)?;
+            }
+            Err(ref e) if is_timeout(e) => deadline.timed_out()?,
+            Err(e) => return Err(Error::Io(e)),
+        }
+    }
+
+    let mut response = Response::new();
+    for event in &events {
+        response.apply_head_event(event)?;
+    }
+    if !response.response_code().is_success() {
+        return Err(Error::Proxy(format!(
+            "CONNECT to {}:{} was refused with {:?}",
+            host, port, response.response_code()
+        )));
+    }
+    debug!("Tunnel to {}:{} opened through {}", host, port, proxy.host());
+    Ok(())
+}
+
+impl Drop for HttpsTransport {
+    fn drop(&mut self) {
         match self.socket.shutdown(Shutdown::Both) {
             Ok(_) => {}
-            Err(e) => warn!("Error shutting down TCP connection: {}", e.to_string()),
-        }
-        Ok(data.len())
-    }
-
-    pub fn socket_is_ready_to_read(&mut self) -> bool {
-        self.socket
-            .set_read_timeout(Some(Duration::new(0, 100)))
-            .unwrap();
-        let mut tmp = vec![1];
-        match self.socket.peek(&mut tmp) {
-            Ok(0) => false,
-            Ok(_) => true,
-            Err(_) => false,
+            Err(e) => trace!("Error shutting down TCP connection: {}", e.to_string()),
         }
     }
 }
 
 impl Read for HttpsTransport {
     fn read(&mut self, data: &mut [u8]) -> Result<usize, IoError> {
-        self.session.read(data)
+        // rustls reads nothing into an empty buffer, which would look like waiting for records
+        if data.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.session.read(data) {
+                Ok(0) => {}
+                Ok(n) => return Ok(n),
+                // the server sent close_notify
+                Err(ref e) if e.kind() == ErrorKind::ConnectionAborted => return Ok(0),
+                Err(e) => return Err(e),
+            }
+            if self.closed {
+                // without close_notify the response may have been cut short
+                return Err(IoError::new(
+                    ErrorKind::UnexpectedEof,
+                    "TLS connection closed without close_notify",
+                ));
+            }
+            self.complete_handshake()?;
+            self.write_records()?;
+            self.read_records()?;
+        }
     }
 }
 
 impl Write for HttpsTransport {
     fn write(&mut self, data: &[u8]) -> Result<usize, IoError> {
+        // finish the handshake and drain earlier records first, so that a large
+        // request body is passed to the socket instead of queueing up in the session
+        self.flush()?;
         self.session.write(data)
     }
 
     fn flush(&mut self) -> Result<(), IoError> {
-        self.session.flush()
+        self.session.flush()?;
+        self.complete_handshake()?;
+        self.write_records()?;
+        Ok(())
     }
 }
+
+#[test]
+fn https_read_into_empty_buffer() {
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use rustls::ClientConfig;
+
+    // the server never answers, so reading records would time out
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let socket = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let config = Config::new().read_timeout(Duration::from_millis(200));
+    let mut transport = HttpsTransport {
+        socket: socket,
+        session: ClientSession::new(&Arc::new(ClientConfig::new()), "localhost"),
+        closed: false,
+        deadline: Deadline::new(&config, None),
+    };
+    assert_eq!(transport.read(&mut []).unwrap(), 0);
+}
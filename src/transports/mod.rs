@@ -1,12 +1,19 @@
+pub mod deadline;
 pub mod http;
 pub mod https;
+pub mod tls;
 
 extern crate rustls;
 
-use std::io::{Error as IoError, Read, Result as IoResult, Write};
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::net::{TcpStream, ToSocketAddrs};
 
+use config::Config;
+use error::Error;
+use tls_info::TlsInfo;
 pub use http::HttpTransport;
 pub use https::HttpsTransport;
+pub use transports::deadline::{is_timeout, Deadline};
 
 //pub enum Transport<'a, T: 'a + Read + Write> {
 pub enum Transport {
@@ -15,14 +22,75 @@ pub enum Transport {
 }
 
 impl Transport {
-    pub fn make_request(&mut self, data: &mut Vec<u8>) -> Result<usize, IoError> {
+    /// Returns true if an idle connection was closed or received unexpected data
+    pub fn is_stale(&mut self) -> bool {
         match *self {
-            Transport::Http(ref mut t) => t.make_request(data),
-            Transport::Https(ref mut t) => t.make_request(data),
+            Transport::Http(ref mut t) => t.is_stale(),
+            Transport::Https(ref mut t) => t.is_stale(),
+        }
+    }
+
+    /// Sets the time limits for the next request on this connection
+    pub fn set_deadline(&mut self, deadline: Deadline) {
+        match *self {
+            Transport::Http(ref mut t) => t.set_deadline(deadline),
+            Transport::Https(ref mut t) => t.set_deadline(deadline),
+        }
+    }
+
+    /// Returns the details of the TLS session, None for plain http
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        match *self {
+            Transport::Http(_) => None,
+            Transport::Https(ref t) => t.tls_info(),
         }
     }
 }
 
+/// Opens a tcp connection, trying each address of `host` within the connect timeout
+pub fn connect_socket(
+    host: &str,
+    port: u16,
+    config: &Config,
+    deadline: &Deadline,
+) -> Result<TcpStream, Error> {
+    let addrs = format!("{}:{}", host, port)
+        .to_socket_addrs()
+        .map_err(Error::Connect)?;
+    let mut last_error = IoError::new(ErrorKind::NotFound, "Host has no addresses");
+    for addr in addrs {
+        let result = match deadline.connect_timeout(config.get_connect_timeout())? {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+            None => TcpStream::connect(&addr),
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(e) => {
+                debug!("Unable to connect to {}: {}", addr, e);
+                last_error = e;
+            }
+        }
+    }
+    if is_timeout(&last_error) {
+        Err(Error::Timeout)
+    } else {
+        Err(Error::Connect(last_error))
+    }
+}
+
+/// Checks an idle socket without blocking, it should have nothing to read
+pub fn socket_is_stale(socket: &TcpStream) -> bool {
+    if socket.set_nonblocking(true).is_err() {
+        return true;
+    }
+    let mut tmp = [0u8; 1];
+    let stale = match socket.peek(&mut tmp) {
+        Err(ref e) if e.kind() == ErrorKind::WouldBlock => false,
+        _ => true,
+    };
+    socket.set_nonblocking(false).is_err() || stale
+}
+
 impl Read for Transport {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
         match *self {
@@ -47,25 +115,3 @@ impl Write for Transport {
         }
     }
 }
-
-pub fn find_sub_vector(large: &Vec<u8>, small: &Vec<u8>) -> Option<usize> {
-    if large.len() < small.len() {
-        return None;
-    } else {
-        let mut i = 0;
-        while i < (large.len() - small.len()) + 1 {
-            let tmp: Vec<u8> = large
-                .iter()
-                .skip(i)
-                .take(small.len())
-                .map(|&i| i.to_owned())
-                .collect();
-
-            if &tmp == small {
-                return Some(i + small.len());
-            }
-            i += 1;
-        }
-    }
-    None
-}
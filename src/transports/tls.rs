@@ -0,0 +1,166 @@
+use std::env;
+use std::net::IpAddr;
+use std::sync::{Arc, Once, ONCE_INIT};
+
+use config::Config;
+use error::Error;
+use pinning::PinningVerifier;
+use revocation::RevocationVerifier;
+use session_cache::SessionCache;
+use rustls::{
+    Certificate, ClientConfig, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError,
+    WebPKIVerifier,
+};
+use trust::{root_store, RootSource};
+use verify::IpAddressVerifier;
+
+pub struct UnsafeCertVerifier {}
+
+impl ServerCertVerifier for UnsafeCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _: &RootCertStore,
+        _: &[Certificate],
+        _: &str,
+        _: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// The rustls configurations a client shares between its https connections
+#[derive(Clone)]
+pub struct TlsConfigs {
+    sni: Arc<ClientConfig>,
+    no_sni: Arc<ClientConfig>,
+}
+
+impl TlsConfigs {
+    /// Uses the rustls configuration given to `config`, or builds one from its settings
+    pub fn new(config: &Config) -> Result<TlsConfigs, Error> {
+        let sni = match config.get_tls_config() {
+            Some(tls) => {
+                let replaced = replaced_settings(config);
+                if !replaced.is_empty() {
+                    return Err(Error::Config(format!(
+                        "a caller built rustls configuration cannot be combined with {}",
+                        replaced.join(", ")
+                    )));
+                }
+                tls.clone()
+            }
+            None => Arc::new(build_client_config(config)?),
+        };
+        // SNI only carries host names (RFC 6066 section 3)
+        let mut no_sni = (*sni).clone();
+        no_sni.enable_sni = false;
+        Ok(TlsConfigs {
+            sni: sni,
+            no_sni: Arc::new(no_sni),
+        })
+    }
+
+    /// Returns the configuration for a connection that verifies `server_name`
+    pub fn for_server(&self, server_name: &str) -> Arc<ClientConfig> {
+        if server_name.parse::<IpAddr>().is_ok() {
+            self.no_sni.clone()
+        } else {
+            self.sni.clone()
+        }
+    }
+}
+
+/// Returns the settings made on `config` that only shape the rustls configuration hasty builds
+fn replaced_settings(config: &Config) -> Vec<&'static str> {
+    let mut replaced = Vec::new();
+    if config.allows_insecure_https() {
+        replaced.push("insecure https");
+    }
+    match config.root_sources() {
+        [RootSource::System] => {}
+        _ => replaced.push("trust anchors"),
+    }
+    if !config.pins().is_empty() {
+        replaced.push("pins");
+    }
+    if !config.crl_sources().is_empty() {
+        replaced.push("revocation lists");
+    }
+    if config.get_client_identity().is_some() {
+        replaced.push("a client identity");
+    }
+    if config.get_session_cache_size() != Config::new().get_session_cache_size()
+        || config.get_session_cache_file().is_some()
+    {
+        replaced.push("session cache settings");
+    }
+    if config.get_tls_customizer().is_some() {
+        replaced.push("a customizer");
+    }
+    replaced
+}
+
+/// Builds a rustls configuration from the trust, pinning, revocation and identity settings
+pub fn build_client_config(config: &Config) -> Result<ClientConfig, Error> {
+    // rustls 0.11 has no KeyLog hook, so say that the variable is ignored rather than stay silent
+    static KEY_LOG_WARNING: Once = ONCE_INIT;
+    if env::var_os("SSLKEYLOGFILE").is_some() {
+        KEY_LOG_WARNING.call_once(|| warn!("SSLKEYLOGFILE is set but TLS key logging is unsupported"));
+    }
+    let mut tls = ClientConfig::new();
+    let mut verifier: Arc<dyn ServerCertVerifier> = if config.allows_insecure_https() {
+        Arc::new(UnsafeCertVerifier {})
+    } else {
+        match root_store(config.root_sources()) {
+            Ok(store) => tls.root_store = store,
+            // pinned hosts are verified without trust anchors
+            Err(ref e) if !config.pins().is_empty() => debug!("{}", e),
+            Err(e) => return Err(e),
+        }
+        Arc::new(IpAddressVerifier::new(Arc::new(WebPKIVerifier {})))
+    };
+    if !config.pins().is_empty() {
+        let pinning =
+            PinningVerifier::new(config.pins().clone(), config.get_pin_fallback(), verifier);
+        verifier = Arc::new(pinning);
+    }
+    // revocation applies to pinned certificates too, but not with verification off
+    if !config.crl_sources().is_empty() && !config.allows_insecure_https() {
+        let revocation = RevocationVerifier::new(
+            config.crl_sources(),
+            config.get_revocation_mode(),
+            verifier,
+        )?;
+        verifier = Arc::new(revocation);
+    }
+    tls.dangerous().set_certificate_verifier(verifier);
+    if let Some(identity) = config.get_client_identity() {
+        tls.set_single_client_cert(identity.certs(), identity.key());
+    }
+    // one cache for every connection of the client, so repeat connections resume
+    let cache = match config.get_session_cache_file() {
+        Some(path) => SessionCache::with_file(config.get_session_cache_size(), path),
+        None => SessionCache::new(config.get_session_cache_size()),
+    };
+    tls.set_persistence(Arc::new(cache));
+    if let Some(customize) = config.get_tls_customizer() {
+        customize(&mut tls);
+    }
+    Ok(tls)
+}
+
+#[test]
+fn caller_tls_config_conflicts() {
+    use pinning::Pin;
+
+    let tls = Arc::new(ClientConfig::new());
+    assert!(TlsConfigs::new(&Config::new().tls_config(tls.clone())).is_ok());
+    let config = Config::new()
+        .tls_config(tls)
+        .pin("localhost", Pin::certificate(b"certificate"))
+        .add_crl_der(b"list");
+    match TlsConfigs::new(&config) {
+        Err(Error::Config(m)) => assert!(m.ends_with("pins, revocation lists")),
+        _ => panic!("expected the combination to be refused"),
+    }
+}
@@ -0,0 +1,178 @@
+#[cfg(feature = "webpki-roots")]
+extern crate webpki_roots;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+
+use rustls::{Certificate, RootCertStore};
+
+use error::Error;
+
+// well known CA bundles, the first one found is used
+const SYSTEM_BUNDLES: &[&str] = &[
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/pki/tls/cacert.pem",
+    "/etc/ssl/cert.pem",
+];
+
+// directories of individual CA certificates, used when no bundle is found
+const SYSTEM_DIRS: &[&str] = &["/etc/ssl/certs", "/etc/pki/tls/certs"];
+
+/// A source of trust anchors for verifying https servers
+#[derive(Clone, Debug)]
+pub enum RootSource {
+    /// The certificates installed on the system, `SSL_CERT_FILE` and `SSL_CERT_DIR` override the location
+    System,
+    /// A PEM bundle file, or a directory of PEM files
+    Path(PathBuf),
+    /// A single DER encoded certificate
+    Der(Vec<u8>),
+    /// One or more PEM encoded certificates
+    Pem(Vec<u8>),
+    /// The Mozilla root program bundle compiled into the crate
+    #[cfg(feature = "webpki-roots")]
+    Builtin,
+}
+
+/// Builds a root store from every source, failing if it ends up empty
+pub fn root_store(sources: &[RootSource]) -> Result<RootCertStore, Error> {
+    let mut store = RootCertStore::empty();
+    for source in sources {
+        match *source {
+            RootSource::System => add_system(&mut store),
+            RootSource::Path(ref path) => add_path(&mut store, path)?,
+            RootSource::Der(ref der) => store
+                .add(&Certificate(der.clone()))
+                .map_err(|e| Error::Certificate(format!("Invalid DER certificate: {:?}", e)))?,
+            RootSource::Pem(ref pem) => {
+                if add_pem(&mut store, &pem[..]) == 0 {
+                    return Err(Error::Certificate("No certificates found in PEM data".to_owned()));
+                }
+            }
+            #[cfg(feature = "webpki-roots")]
+            RootSource::Builtin => store.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS),
+        }
+    }
+    if store.len() == 0 {
+        return Err(Error::Certificate(
+            "No trust anchors were loaded to verify https servers".to_owned(),
+        ));
+    }
+    Ok(store)
+}
+
+/// Adds the PEM certificates in `data`, returning how many were added
+fn add_pem(store: &mut RootCertStore, data: &[u8]) -> usize {
+    match store.add_pem_file(&mut Cursor::new(data)) {
+        Ok((added, skipped)) => {
+            if skipped > 0 {
+                debug!("Skipped {} unusable trust anchors", skipped);
+            }
+            added
+        }
+        Err(_) => 0,
+    }
+}
+
+fn add_file(store: &mut RootCertStore, path: &Path) -> Result<usize, Error> {
+    let file = File::open(path)
+        .map_err(|e| Error::Certificate(format!("{}: {}", path.display(), e)))?;
+    let mut reader = BufReader::new(file);
+    match store.add_pem_file(&mut reader) {
+        Ok((added, _)) => Ok(added),
+        Err(_) => Err(Error::Certificate(format!("{}: invalid PEM data", path.display()))),
+    }
+}
+
+fn add_dir(store: &mut RootCertStore, path: &Path) -> Result<usize, Error> {
+    let entries = fs::read_dir(path)
+        .map_err(|e| Error::Certificate(format!("{}: {}", path.display(), e)))?;
+    let mut added = 0;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        // directories such as /etc/ssl/certs hold other files too, those are skipped
+        match add_file(store, &path) {
+            Ok(n) => added += n,
+            Err(e) => trace!("Skipping {}", e),
+        }
+    }
+    Ok(added)
+}
+
+fn add_path(store: &mut RootCertStore, path: &Path) -> Result<(), Error> {
+    let added = if path.is_dir() {
+        add_dir(store, path)?
+    } else {
+        add_file(store, path)?
+    };
+    if added == 0 {
+        return Err(Error::Certificate(format!(
+            "{}: no certificates found",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+fn add_system(store: &mut RootCertStore) {
+    let mut added = 0;
+    if let Some(file) = env::var_os("SSL_CERT_FILE") {
+        added += add_file(store, Path::new(&file)).unwrap_or(0);
+    }
+    if let Some(dir) = env::var_os("SSL_CERT_DIR") {
+        added += add_dir(store, Path::new(&dir)).unwrap_or(0);
+    }
+    if added > 0 {
+        return;
+    }
+    for bundle in SYSTEM_BUNDLES {
+        if let Ok(n) = add_file(store, Path::new(bundle)) {
+            if n > 0 {
+                debug!("Loaded {} system trust anchors from {}", n, bundle);
+                return;
+            }
+        }
+    }
+    for dir in SYSTEM_DIRS {
+        if let Ok(n) = add_dir(store, Path::new(dir)) {
+            if n > 0 {
+                debug!("Loaded {} system trust anchors from {}", n, dir);
+                return;
+            }
+        }
+    }
+    warn!("No system trust anchors were found");
+}
+
+#[test]
+fn trust_pem_in_memory() {
+    let pem = include_bytes!("../test_server/https.pem").to_vec();
+    let store = root_store(&[RootSource::Pem(pem)]).unwrap();
+    assert_eq!(store.len(), 1);
+}
+
+#[test]
+fn trust_pem_file() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_server/https.pem");
+    let store = root_store(&[RootSource::Path(path)]).unwrap();
+    assert_eq!(store.len(), 1);
+}
+
+#[test]
+fn trust_nothing_loaded() {
+    match root_store(&[]) {
+        Err(Error::Certificate(_)) => {}
+        _ => panic!("expected an empty store to fail"),
+    }
+    match root_store(&[RootSource::Path(PathBuf::from("/nonexistent/ca.pem"))]) {
+        Err(Error::Certificate(_)) => {}
+        _ => panic!("expected a missing file to fail"),
+    }
+}
@@ -0,0 +1,77 @@
+extern crate untrusted;
+extern crate webpki;
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::{Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+
+use der::ip_addresses;
+
+static SUPPORTED_SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA1,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// Verifies servers reached by an ip address against the iPAddress entries of their
+/// certificate, and leaves servers reached by name to `inner`
+pub struct IpAddressVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+}
+
+impl IpAddressVerifier {
+    pub fn new(inner: Arc<dyn ServerCertVerifier>) -> IpAddressVerifier {
+        IpAddressVerifier { inner: inner }
+    }
+}
+
+impl ServerCertVerifier for IpAddressVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented: &[Certificate],
+        dns_name: &str,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let ip: IpAddr = match dns_name.parse() {
+            Ok(ip) => ip,
+            Err(_) => return self.inner.verify_server_cert(roots, presented, dns_name, ocsp_response),
+        };
+        verify_chain(roots, presented)?;
+        if ip_addresses(&presented[0].0).contains(&ip) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TLSError::WebPKIError(webpki::Error::CertNotValidForName))
+        }
+    }
+}
+
+/// Checks that the presented chain leads to a trust anchor, without looking at names
+fn verify_chain(roots: &RootCertStore, presented: &[Certificate]) -> Result<(), TLSError> {
+    if presented.is_empty() {
+        return Err(TLSError::NoCertificatesPresented);
+    }
+    let cert = webpki::EndEntityCert::from(untrusted::Input::from(&presented[0].0))
+        .map_err(TLSError::WebPKIError)?;
+    let intermediates: Vec<untrusted::Input> = presented[1..]
+        .iter()
+        .map(|c| untrusted::Input::from(&c.0))
+        .collect();
+    let anchors: Vec<webpki::TrustAnchor> = roots.roots.iter().map(|r| r.to_trust_anchor()).collect();
+    let now = webpki::Time::try_from(SystemTime::now())
+        .map_err(|_| TLSError::General("Unable to read the current time".to_owned()))?;
+    cert.verify_is_valid_tls_server_cert(
+        SUPPORTED_SIG_ALGS,
+        &webpki::TLSServerTrustAnchors(&anchors),
+        &intermediates,
+        now,
+    ).map_err(TLSError::WebPKIError)
+}
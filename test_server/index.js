@@ -1,4 +1,7 @@
+const http = require('http')
 const https = require('https')
+const net = require('net')
+const url = require('url')
 const express = require('express')
 const fs = require('fs')
 const app = express()
@@ -25,6 +28,11 @@ app.get('/basic_get', function (req, res) {
   res.send('success')
 })
 
+// tells whether the tls session of the connection was resumed
+app.get('/session_reused', function (req, res) {
+  res.send(String(req.socket.isSessionReused()))
+})
+
 app.post('/basic_post', function (req, res) {
   console.log('got a post: ' + req.body)
   var payload = {
@@ -38,6 +46,37 @@ app.post('/basic_409', function(req, res) {
     res.status(409).send()
 });
 
+app.get('/redirect_chain', function(req, res) {
+    res.redirect(302, '/redirect_once')
+});
+
+app.get('/redirect_once', function(req, res) {
+    res.redirect(301, '/basic_get')
+});
+
+app.post('/redirect_see_other', function(req, res) {
+    res.redirect(303, '/basic_get')
+});
+
+app.get('/client_cert', function(req, res) {
+    var cert = req.socket.getPeerCertificate ? req.socket.getPeerCertificate() : null
+    res.send(cert && cert.subject ? cert.subject.CN : 'none')
+});
+
+app.get('/trailers', function(req, res) {
+    res.writeHead(200, { 'Transfer-Encoding': 'chunked', 'Trailer': 'X-Checksum' })
+    res.write('chunked ')
+    res.write('body')
+    res.addTrailers({ 'X-Checksum': 'abc123' })
+    res.end()
+});
+
+app.get('/slow_get', function(req, res) {
+    setTimeout(function () {
+        res.send('success')
+    }, 2000)
+});
+
 app.listen(3000, function () {
   console.log('hasty-rs test server listening on 3000!')
 })
@@ -45,3 +84,84 @@ app.listen(3000, function () {
 https.createServer(options, app).listen(3001, function () {
   console.log('hasty-rs test tls server listening on 3001')
 })
+
+// asks for a client certificate, any certificate is accepted
+https.createServer(Object.assign({ requestCert: true, rejectUnauthorized: false }, options), app).listen(3002, function () {
+  console.log('hasty-rs test mutual tls server listening on 3002')
+})
+
+// a certificate for 127.0.0.1 and ::1, issued by ca.pem
+https.createServer({ key: fs.readFileSync('./ip.key'), cert: fs.readFileSync('./ip.pem') }, app).listen(3003, function () {
+  console.log('hasty-rs test ip address tls server listening on 3003')
+})
+
+// a certificate for 127.0.0.1 issued by crl_ca.pem, revoked in revoked.crl
+https.createServer({ key: fs.readFileSync('./revoked.key'), cert: fs.readFileSync('./revoked.pem') }, app).listen(3004, function () {
+  console.log('hasty-rs test revoked certificate tls server listening on 3004')
+})
+
+// an HTTP/1.0 server with an unregistered status, it closes after each response
+net.createServer(function (socket) {
+  socket.once('data', function () {
+    socket.end('HTTP/1.0 299 Custom Status\r\nContent-Length: 7\r\n\r\nsuccess')
+  })
+}).listen(3005, function () {
+  console.log('hasty-rs test http/1.0 server listening on 3005')
+})
+
+// a forward proxy stand-in, it wants the credentials hasty:proxy and
+// resolves the made up host proxied.test to this machine
+const proxyAuth = 'Basic ' + Buffer.from('hasty:proxy').toString('base64')
+
+function proxyTarget(host) {
+  return host === 'proxied.test' ? 'localhost' : host
+}
+
+var proxy = http.createServer(function (req, res) {
+  if (req.headers['proxy-authorization'] !== proxyAuth) {
+    res.writeHead(407, { 'Proxy-Authenticate': 'Basic' })
+    return res.end()
+  }
+  var target = url.parse(req.url)
+  var headers = Object.assign({}, req.headers)
+  delete headers['proxy-authorization']
+  var upstream = http.request({
+    host: proxyTarget(target.hostname),
+    port: target.port || 80,
+    path: target.path,
+    method: req.method,
+    headers: headers
+  }, function (up) {
+    res.writeHead(up.statusCode, up.headers)
+    up.pipe(res)
+  })
+  upstream.on('error', function () {
+    res.writeHead(502)
+    res.end()
+  })
+  req.pipe(upstream)
+})
+
+proxy.on('connect', function (req, socket, head) {
+  if (req.headers['proxy-authorization'] !== proxyAuth) {
+    return socket.end('HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 0\r\n\r\n')
+  }
+  // the Host header has to carry the port of the target as well
+  if (req.headers.host !== req.url) {
+    return socket.end('HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n')
+  }
+  var parts = req.url.split(':')
+  var upstream = net.connect(parts[1], proxyTarget(parts[0]), function () {
+    socket.write('HTTP/1.1 200 Connection Established\r\n\r\n')
+    upstream.write(head)
+    upstream.pipe(socket)
+    socket.pipe(upstream)
+  })
+  upstream.on('error', function () {
+    socket.end('HTTP/1.1 502 Bad Gateway\r\n\r\n')
+  })
+})
+
+proxy.listen(3128, function () {
+  console.log('hasty-rs test proxy listening on 3128')
+})